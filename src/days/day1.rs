@@ -11,7 +11,22 @@ pub fn part1() {
 
 #[allow(dead_code)]
 pub fn part2() {
-    println!("{}", solve_part2(get_puzzle_input()));
+    match find_first_repeat(&get_puzzle_input()) {
+        Some(repeat) => println!(
+            "{} (change {} of pass {})",
+            repeat.frequency, repeat.change_index + 1, repeat.iteration + 1
+        ),
+        None => println!("No frequency is ever reached twice")
+    }
+}
+
+/// The first frequency that is reached twice, along with the change that reached it. Both the
+/// iteration over the list of changes and the index of the change in the list start at 0.
+#[derive(Debug, Eq, PartialEq)]
+struct Repeat {
+    frequency: i64,
+    iteration: usize,
+    change_index: usize,
 }
 
 fn solve_part1(changes: Vec<i32>) -> i32 {
@@ -19,26 +34,74 @@ fn solve_part1(changes: Vec<i32>) -> i32 {
     changes.into_iter().sum()
 }
 
-fn solve_part2(changes: Vec<i32>) -> i32 {
-    // Track all observed frequencies with a hash set
-    let mut seen = HashSet::new();
+/// Find the first frequency that is reached twice without simulating the iterations. After `t` full
+/// iterations and `j` more changes the frequency is `t * drift + prefix[j]`, where drift is the sum
+/// of all changes. Apart from repeats within the first iteration, a later frequency can only equal
+/// an earlier one if their prefix sums differ by a multiple of the drift. So the prefix sums are
+/// grouped by their remainder modulo the drift, and within each group only neighbours need to be
+/// compared. Returns None if no frequency ever repeats.
+fn find_first_repeat(changes: &[i32]) -> Option<Repeat> {
+    let n = changes.len();
+    if n == 0 {
+        return None;
+    }
+
+    // The frequency after every number of changes within the first iteration, starting with 0
+    let mut prefix = Vec::with_capacity(n);
+    let mut frequency = 0i64;
+    for change in changes {
+        prefix.push(frequency);
+        frequency += *change as i64;
+    }
+    let drift = frequency;
+
+    // The step (number of changes applied) at which the repeat happens, and its frequency
+    let mut first: Option<(usize, i64)> = None;
 
-    // Start at 0 and mark it as seen
-    let mut frequency = 0;
-    seen.insert(frequency);
+    // Repeats within the first iteration always come first
+    let mut seen = HashSet::new();
+    for (step, p) in prefix.iter().enumerate() {
+        if !seen.insert(*p) {
+            first = Some((step, *p));
+            break;
+        }
+    }
 
-    // Cycle through the list of changes infinitely
-    let mut change_cycle = changes.into_iter().cycle();
+    if first.is_none() && drift == 0 {
+        // Every iteration ends where it started
+        first = Some((n, 0));
+    }
 
-    loop {
-        frequency += change_cycle.next().unwrap();
-        let added = seen.insert(frequency);
+    if first.is_none() {
+        // Sort by remainder, and then in the direction of the drift, so that each prefix sum is
+        // followed by the next one that it will reach after some number of iterations
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by_key(|j| (prefix[*j].rem_euclid(drift), prefix[*j] * drift.signum()));
 
-        if !added {
-            // This was seen before, so the current frequency is the answer
-            break frequency;
+        for pair in order.windows(2) {
+            let (j, i) = (pair[0], pair[1]);
+            let diff = prefix[i] - prefix[j];
+            if diff.rem_euclid(drift) != 0 {
+                // Different remainders
+                continue;
+            }
+            let iterations = (diff / drift) as usize;
+            let step = iterations * n + j;
+            let is_earlier = match first {
+                Some((earliest, _)) => step < earliest,
+                None => true
+            };
+            if is_earlier {
+                first = Some((step, prefix[i]));
+            }
         }
     }
+
+    first.map(|(step, frequency)| Repeat {
+        frequency,
+        iteration: (step - 1) / n,
+        change_index: (step - 1) % n,
+    })
 }
 
 fn get_puzzle_input() -> Vec<i32> {
@@ -71,20 +134,59 @@ mod test {
     #[test]
     fn test_part2() {
         assert_eq!(
-            solve_part2(vec![1, -1]),
-            0
+            find_first_repeat(&[1, -1]).map(|r| r.frequency),
+            Some(0)
+        );
+        assert_eq!(
+            find_first_repeat(&[3, 3, 4, -2, -4]).map(|r| r.frequency),
+            Some(10)
+        );
+        assert_eq!(
+            find_first_repeat(&[-6, 3, 8, 5, -6]).map(|r| r.frequency),
+            Some(5)
         );
         assert_eq!(
-            solve_part2(vec![3, 3, 4, -2, -4]),
-            10
+            find_first_repeat(&[7, 7, -2, -7, -4]).map(|r| r.frequency),
+            Some(14)
         );
+    }
+
+    #[test]
+    fn test_first_repeat() {
         assert_eq!(
-            solve_part2(vec![-6, 3, 8, 5, -6]),
-            5
+            find_first_repeat(&[3, 3, 4, -2, -4]),
+            Some(Repeat { frequency: 10, iteration: 1, change_index: 1 })
         );
         assert_eq!(
-            solve_part2(vec![7, 7, -2, -7, -4]),
-            14
+            find_first_repeat(&[1, -1]),
+            Some(Repeat { frequency: 0, iteration: 0, change_index: 1 })
         );
+        assert_eq!(find_first_repeat(&[1, 2, 3]), None);
+        assert_eq!(find_first_repeat(&[]), None);
+
+        // Compare with simulating every step on inputs with positive and negative drift
+        let inputs: Vec<Vec<i32>> = vec![
+            vec![5, -3, 7, -11, 4],
+            vec![-5, 3, -7, 11, -4],
+            vec![100, -99, 2, -98, -5],
+            vec![-2, 1, 1, 1],
+        ];
+        for changes in inputs {
+            let mut seen = HashSet::new();
+            let mut frequency = 0i64;
+            seen.insert(frequency);
+            let mut step = 0;
+            loop {
+                frequency += changes[step % changes.len()] as i64;
+                step += 1;
+                if !seen.insert(frequency) {
+                    break;
+                }
+            }
+
+            let repeat = find_first_repeat(&changes).unwrap();
+            assert_eq!(repeat.frequency, frequency);
+            assert_eq!(repeat.iteration * changes.len() + repeat.change_index + 1, step);
+        }
     }
 }
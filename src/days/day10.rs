@@ -7,34 +7,103 @@ use utils::matrix::Matrix;
 
 #[allow(dead_code)]
 pub fn part1() {
-    println!("{}", solve_both_parts(get_puzzle_input()).0);
+    println!("{}", solve_part1(get_puzzle_input()));
 }
 
 #[allow(dead_code)]
 pub fn part2() {
-    println!("{}", solve_both_parts(get_puzzle_input()).1);
+    println!("{}", solve_part2(get_puzzle_input()));
 }
 
+#[derive(Clone)]
 struct Point {
     p: (i32, i32),
     v: (i32, i32),
 }
 
-fn solve_both_parts(p: Vec<Point>) -> (String, usize) {
-    let mu = mean(p.iter().map(|p| p.p.0 as f32)).unwrap();
-    let ev = mean(p.iter().map(|p| p.v.0 as f32)).unwrap();
-    let t = mean(p.iter().map(|p| (mu - p.p.0 as f32) / (p.v.0 as f32 - ev)))
-        .unwrap().round() as i32;
+/// The letters that messages are made of, 6 wide and 10 high with 2 columns between them
+const FONT_LETTERS: &str = "ABCEFGHJKLNPRXZ";
+const FONT: [&str; 10] = [
+    "..##....#####....####...######..######...####...#....#.....###..#....#..#.......#....#..#####...#####...#....#..######",
+    ".#..#...#....#..#....#..#.......#.......#....#..#....#......#...#...#...#.......##...#..#....#..#....#..#....#.......#",
+    "#....#..#....#..#.......#.......#.......#.......#....#......#...#..#....#.......##...#..#....#..#....#...#..#........#",
+    "#....#..#....#..#.......#.......#.......#.......#....#......#...#.#.....#.......#.#..#..#....#..#....#...#..#.......#.",
+    "#....#..#####...#.......#####...#####...#.......######......#...##......#.......#.#..#..#####...#####.....##.......#..",
+    "######..#....#..#.......#.......#.......#..###..#....#......#...##......#.......#..#.#..#.......#..#......##......#...",
+    "#....#..#....#..#.......#.......#.......#....#..#....#......#...#.#.....#.......#..#.#..#.......#...#....#..#....#....",
+    "#....#..#....#..#.......#.......#.......#....#..#....#..#...#...#..#....#.......#...##..#.......#...#....#..#...#.....",
+    "#....#..#....#..#....#..#.......#.......#...##..#....#..#...#...#...#...#.......#...##..#.......#....#..#....#..#.....",
+    "#....#..#####....####...######..#........###.#..#....#...###....#....#..######..#....#..#.......#....#..#....#..######",
+];
+const LETTER_WIDTH: usize = 6;
+const LETTER_PITCH: usize = 8;
 
-    (format_points(&project(&p, t)), t as usize)
+fn solve_part1(points: Vec<Point>) -> String {
+    let points = project(&points, find_meeting_time(&points) as i32);
+    read_message(&points).unwrap_or_else(|| format_points(&points))
 }
 
-fn mean(iter: impl Iterator<Item=f32>) -> Option<f32> {
-    let (sum, count) = iter.fold((0.0, 0), |(s, c), v| (s + v, c + 1));
-    if count > 0 { Some(sum / count as f32) } else { None }
+fn solve_part2(points: Vec<Point>) -> usize {
+    find_meeting_time(&points)
 }
 
-fn project(points: &Vec<Point>, time: i32) -> Vec<Point> {
+/// Find the first time at which the bounding box of the points is smallest. Its width and height
+/// are both convex over time, as the difference of the largest and smallest of a set of linear
+/// functions, so the first time from which their sum stops decreasing is the minimum. This doesn't
+/// depend on the points moving in any particular direction.
+fn find_meeting_time(points: &[Point]) -> usize {
+    let size = |t: i64| {
+        let xs = points.iter().map(|p| p.p.0 as i64 + p.v.0 as i64 * t);
+        let ys = points.iter().map(|p| p.p.1 as i64 + p.v.1 as i64 * t);
+        xs.clone().max().unwrap() - xs.min().unwrap() + ys.clone().max().unwrap() - ys.min().unwrap()
+    };
+    let shrinking = |t: i64| size(t + 1) < size(t);
+
+    // Find a time at which the box is no longer shrinking, then search for the first one
+    let mut hi = 1;
+    while shrinking(hi) {
+        hi *= 2;
+    }
+    let mut lo = 0;
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if shrinking(mid) { lo = mid + 1 } else { hi = mid }
+    }
+    lo as usize
+}
+
+/// Read the message that the points spell, or None if they don't form known letters
+fn read_message(points: &[Point]) -> Option<String> {
+    let ((x_min, x_max), (y_min, y_max)) = get_aabb(points);
+    let width = (x_max - x_min + 1) as usize;
+    let letters = (width + LETTER_PITCH - LETTER_WIDTH) / LETTER_PITCH;
+    if y_max - y_min + 1 != FONT.len() as i32 || letters * LETTER_PITCH - (LETTER_PITCH - LETTER_WIDTH) != width {
+        return None;
+    }
+
+    let mut rows = vec![vec!['.'; width]; FONT.len()];
+    for point in points {
+        rows[(point.p.1 - y_min) as usize][(point.p.0 - x_min) as usize] = '#';
+    }
+
+    let glyph = |rows: &[Vec<char>], i: usize| -> Vec<String> {
+        rows.iter()
+            .map(|row| row[i * LETTER_PITCH..i * LETTER_PITCH + LETTER_WIDTH].iter().collect())
+            .collect()
+    };
+    let font: Vec<_> = FONT.iter().map(|row| row.chars().collect::<Vec<_>>()).collect();
+
+    (0..letters)
+        .map(|i| {
+            let letter = glyph(&rows, i);
+            FONT_LETTERS.chars().enumerate()
+                .find(|(j, _)| glyph(&font, *j) == letter)
+                .map(|(_, c)| c)
+        })
+        .collect()
+}
+
+fn project(points: &[Point], time: i32) -> Vec<Point> {
     points.iter()
         .map(|point| {
             Point {
@@ -45,8 +114,8 @@ fn project(points: &Vec<Point>, time: i32) -> Vec<Point> {
         .collect()
 }
 
-fn format_points(points: &Vec<Point>) -> String {
-    let ((x_min, x_max), (y_min, y_max)) = get_aabb(&points);
+fn format_points(points: &[Point]) -> String {
+    let ((x_min, x_max), (y_min, y_max)) = get_aabb(points);
 
     let mut grid = Matrix::new(
         (y_max - y_min + 1) as usize,
@@ -69,7 +138,7 @@ fn format_points(points: &Vec<Point>) -> String {
     result.trim().to_string()
 }
 
-fn get_aabb(points: &Vec<Point>) -> ((i32, i32), (i32, i32)) {
+fn get_aabb(points: &[Point]) -> ((i32, i32), (i32, i32)) {
     let mut xs: Vec<i32> = points.iter().map(|p| p.p.0).collect();
     let mut ys: Vec<i32> = points.iter().map(|p| p.p.1).collect();
     xs.sort();
@@ -118,10 +187,36 @@ mod test {
 #...#...#.
 #...#..###".trim());
 
-        assert_eq!(
-            solve_both_parts(parse_input(get_test_data())),
-            (expected, 3)
-        );
+        // The example uses a smaller font, so it can't be read
+        assert_eq!(solve_part1(parse_input(get_test_data())), expected);
+        assert_eq!(solve_part2(parse_input(get_test_data())), 3);
+    }
+
+    #[test]
+    fn test_equal_x_velocity() {
+        // The points all move at the same speed horizontally, and line up at time 5
+        let points: Vec<_> = (0..10)
+            .map(|i| Point { p: (i, -5 * (i % 4 - 2)), v: (3, i % 4 - 2) })
+            .collect();
+        assert_eq!(find_meeting_time(&points), 5);
+    }
+
+    #[test]
+    fn test_read_message() {
+        // Spell out the font itself in points that fly in from far away
+        let points: Vec<_> = FONT.iter().enumerate()
+            .flat_map(|(y, row)| row.chars().enumerate()
+                .filter(|(_, c)| *c == '#')
+                .map(move |(x, _)| (x as i32, y as i32)))
+            .enumerate()
+            .map(|(i, (x, y))| {
+                let v = ((i % 7) as i32 - 3, (i % 5) as i32 - 2);
+                Point { p: (x - v.0 * 10000, y - v.1 * 10000), v }
+            })
+            .collect();
+
+        assert_eq!(solve_part2(points.clone()), 10000);
+        assert_eq!(solve_part1(points), FONT_LETTERS);
     }
 
     fn get_test_data() -> String {
@@ -1,4 +1,7 @@
 //! Solutions for https://adventofcode.com/2018/day/11
+use utils::matrix::Matrix;
+use utils::summed_area_table::SummedAreaTable;
+
 #[allow(dead_code)]
 pub fn part1() {
     println!("{:?}", solve_part1(300, 8868));
@@ -10,13 +13,15 @@ pub fn part2() {
 }
 
 fn solve_part1(grid_size: usize, sn: usize) -> (usize, usize, i32) {
-    solve(grid_size, sn, 3)
+    solve(&build_table(grid_size, sn), 3)
 }
 
 fn solve_part2(grid_size: usize, sn: usize) -> (usize, usize, usize, i32) {
+    let table = build_table(grid_size, sn);
+
     let mut best = (0, 0, 0, -10000);
-    for bs in 1..=300 {
-        let (x, y, p) = solve(grid_size, sn, bs);
+    for bs in 1..=grid_size {
+        let (x, y, p) = solve(&table, bs);
         if p > best.3 {
             best = (x, y, bs, p);
         }
@@ -24,39 +29,21 @@ fn solve_part2(grid_size: usize, sn: usize) -> (usize, usize, usize, i32) {
     best
 }
 
-fn solve(grid_size: usize, sn: usize, bs: usize) -> (usize, usize, i32) {
-    let mut best = (0, 0, -10000);
-
-    // Create a list of sums of vertical slices of the power levels starting at y=0 and height of
-    // the block size. One for every x coordinate.
-    let mut chunks: Vec<_> = (0..grid_size).map(|x| {
-        (0..bs).map(|y| power_level(x, y, sn)).sum::<i32>()
-    }).collect();
-
-    for y in 0..grid_size - (bs - 1) {
-        // Calculate the total power of the first block at (x=0, y)
-        let mut total = chunks.iter().take(bs).sum::<i32>();
-        // For every x, update the total power by removing the leftmost chunk and adding one to the
-        // right.
-        for x in 0..grid_size - (bs - 1) {
-            // First update the best score
-            if total > best.2 {
-                best = (x, y, total);
-            }
-            if x < grid_size - bs {
-                total += chunks[x + bs] - chunks[x];
-            }
-        }
+fn solve(table: &SummedAreaTable<i32>, bs: usize) -> (usize, usize, i32) {
+    let ((y, x), total) = table.best_window(bs).unwrap();
+    (x, y, total)
+}
 
-        // After a horizontal scan, move all the chunks one position down by subtracting the topmost
-        // row of power levels and adding one to the bottom.
-        if y < grid_size - bs {
-            for x in 0..grid_size - (bs - 1) {
-                chunks[x] += power_level(x, y + bs, sn) - power_level(x, y, sn);
-            }
+/// Build a summed-area table of the power levels of all cells in the grid, so that the total power
+/// of any block can be looked up in constant time.
+fn build_table(grid_size: usize, sn: usize) -> SummedAreaTable<i32> {
+    let mut levels = Matrix::new(grid_size, grid_size, 0);
+    for y in 0..grid_size {
+        for x in 0..grid_size {
+            levels[(y, x)] = power_level(x, y, sn);
         }
     }
-    best
+    SummedAreaTable::new(&levels)
 }
 
 fn power_level(x: usize, y: usize, sn: usize) -> i32 {
@@ -1,4 +1,8 @@
 //! Solutions for https://adventofcode.com/2018/day/12
+use utils::automaton::Automaton;
+use utils::automaton::Boundary;
+use utils::automaton::Neighbourhood;
+use utils::automaton::Shortcut;
 use utils::data::load_data;
 use utils::data::non_empty_lines;
 
@@ -19,85 +23,31 @@ struct Scenario {
 }
 
 fn solve(scenario: Scenario, generations: usize) -> i64 {
-    let max_generations = 10000;
+    let Scenario { initial, kernels } = scenario;
 
-    let offset = 2 * max_generations;
-    let lower = -(offset as i64);
-    let upper = scenario.initial.len() as i64 + offset as i64;
+    // A pot will contain a plant if the pots around it match any of the kernels
+    let rule = move |_, window: &[u8]| kernels.iter().any(|k| k.as_slice() == window) as u8;
 
-    let mut source = vec![0; (upper - lower) as usize];
-    let mut target = vec![0; (upper - lower) as usize];
+    let mut automaton = Automaton::from_row(
+        &initial,
+        Neighbourhood::window(2),
+        Boundary::Expand(0),
+        rule,
+    );
 
-    for (i, p) in scenario.initial.iter().enumerate() {
-        source[i + offset] = *p;
-    }
-
-    let mut gv = gen_value(&source, lower, upper);
-    let mut last_delta_gv = 0;
-    let mut same_delta_count = 0;
-
-    for g in 0..generations {
-        if g == max_generations {
-            panic!("Did not find a solution within {} generations", max_generations);
-        }
-        let r = apply_generation(source, target, &scenario.kernels);
-        source = r.0;
-        target = r.1;
-
-        let mut gv_new = gen_value(&source, lower, upper);
-        let delta_gv = gv_new - gv;
-        gv = gv_new;
-
-        if delta_gv == last_delta_gv {
-            same_delta_count += 1;
-            if same_delta_count == 10 {
-                // Assume the same delta will keep repeating.
-                let remaining_gens = generations - g - 1;
-                return gv + remaining_gens as i64 * delta_gv;
-            }
-        } else {
-            same_delta_count = 0;
-        }
-
-        last_delta_gv = delta_gv;
-    }
-
-    gen_value(&source, lower, upper)
+    // Eventually the plants settle into a pattern that just moves along the row, so the value of a
+    // generation will keep growing by the same amount.
+    automaton.advance(generations, Shortcut::LinearGrowth { repeats: 10 }, gen_value).value
 }
 
-fn gen_value(gen: &Vec<u8>, lower: i64, upper: i64) -> i64 {
-    gen.iter().zip(lower..upper)
+fn gen_value(automaton: &Automaton<u8>) -> i64 {
+    let first_pot = automaton.origin().1;
+    automaton.grid().rows().next().unwrap().into_iter()
+        .zip(first_pot..)
         .map(|(has_plant, potno)| potno * *has_plant as i64)
         .sum::<i64>()
 }
 
-fn apply_generation(source: Vec<u8>, mut target: Vec<u8>, kernels: &Vec<Vec<u8>>) -> (Vec<u8>, Vec<u8>) {
-    for i in 0..source.len() {
-        let mut matches = 0;
-        for kernel in kernels {
-            if kernel_matches(&source, i, kernel) {
-                matches = 1;
-                break;
-            }
-        }
-        target[i] = matches;
-    }
-
-    (target, source)
-}
-
-fn kernel_matches(state: &Vec<u8>, offset: usize, kernel: &Vec<u8>) -> bool {
-    kernel.iter().enumerate()
-        .all(|(i, z)| {
-            let si = i as i64 - 2 + offset as i64;
-            if si < 0 || si >= state.len() as i64 {
-                false
-            } else {
-                state[si as usize] == *z
-            }
-        })
-}
-
 fn get_puzzle_input() -> Scenario {
     parse_input(load_data("day12"))
 }
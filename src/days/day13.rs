@@ -1,10 +1,11 @@
 //! Solutions for https://adventofcode.com/2018/day/13
 use std::collections::HashMap;
-use std::collections::HashSet;
 use std::fmt::Display;
 use std::fmt::Error;
 use std::fmt::Formatter;
-use std::fmt::Write;
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::Write;
 
 use utils::data::load_data;
 use utils::data::non_empty_lines;
@@ -22,20 +23,108 @@ pub fn part2() {
     println!("{},{}", x, y);
 }
 
+/// Print every crash that happens until at most one cart is left
+#[allow(dead_code)]
+pub fn log_part2() {
+    for crash in crash_log(get_puzzle_input(), MAX_TICKS) {
+        println!("{}", crash);
+    }
+}
+
+/// Print every crash that happens when carts turn right first instead of left
+#[allow(dead_code)]
+pub fn log_part2_mirrored() {
+    let mut scenario = get_puzzle_input();
+    scenario.set_policy(cycle_turns(vec![Turn::Right, Turn::Straight, Turn::Left]));
+    for crash in crash_log(scenario, MAX_TICKS) {
+        println!("{}", crash);
+    }
+}
+
+/// Write the state of the track after every tick up to the first crash to a file
+#[allow(dead_code)]
+pub fn replay_part1() {
+    let mut scenario = get_puzzle_input();
+    let mut replay = String::new();
+    writeln!(replay, "Initially:\n{}", scenario).unwrap();
+    loop {
+        let crashes = scenario.do_tick();
+        writeln!(replay, "After tick {}:\n{}", scenario.tick, scenario).unwrap();
+        if !crashes.is_empty() {
+            break;
+        }
+    }
+
+    let path = "day13_replay.txt";
+    let mut file = File::create(path).unwrap();
+    file.write_all(replay.as_bytes()).unwrap();
+    println!("Replay written to {}", path);
+}
+
+type Coord = (usize, usize);
+
+/// How long to look for crashes before giving up
+const MAX_TICKS: usize = 100_000;
+
+/// What a cart does when it arrives at an intersection
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Turn {
+    Left,
+    Straight,
+    Right,
+}
+
+/// Decides how a cart turns at an intersection, given the id of the cart and the number of
+/// intersections it passed before
+pub type TurnPolicy = Box<dyn Fn(usize, usize) -> Turn>;
+
+/// A policy where every cart repeats the same sequence of turns, which can't be empty
+pub fn cycle_turns(turns: Vec<Turn>) -> TurnPolicy {
+    assert!(!turns.is_empty(), "A turn cycle needs at least one turn");
+    Box::new(move |_, count| turns[count % turns.len()])
+}
+
 struct Scenario {
     track: Matrix<TrackCell>,
     carts: Vec<Cart>,
+    policy: TurnPolicy,
+    // The number of ticks that were performed so far
+    tick: usize,
+    // Where carts crashed during the last tick, which are drawn until the next tick
+    crash_sites: Vec<Coord>,
 }
 
 #[derive(Debug, Eq, PartialEq)]
 struct Cart {
+    // The index of the cart in the input, in reading order
+    id: usize,
     // y, x
-    pos: (usize, usize),
+    pos: Coord,
     // y, x
     dir: (i32, i32),
     intersection_count: usize,
 }
 
+/// Two carts that ran into each other
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Crash {
+    /// The tick during which the crash happened, starting at 1
+    pub tick: usize,
+    /// y, x
+    pub pos: Coord,
+    /// The ids of the cart that moved and the cart it ran into
+    pub carts: [usize; 2],
+}
+
+impl Display for Crash {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(
+            f, "Tick {}: cart {} crashed into cart {} at {},{}",
+            self.tick, self.carts[0], self.carts[1], self.pos.1, self.pos.0
+        )
+    }
+}
+
 enum GridCell {
     // Direction y, x
     Cart(i32, i32),
@@ -64,36 +153,80 @@ impl Display for TrackCell {
     }
 }
 
-fn solve_part1(mut scenario: Scenario) -> (usize, usize) {
-    loop {
-        if let Some(pos) = do_tick(&mut scenario) {
-            break pos;
+impl Display for Scenario {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        let carts: HashMap<_, _> = self.carts.iter().map(|c| (c.pos, c.dir)).collect();
+
+        for y in 0..self.track.height {
+            for x in 0..self.track.width {
+                if self.crash_sites.contains(&(y, x)) {
+                    f.write_char('X')?;
+                } else if let Some(dir) = carts.get(&(y, x)) {
+                    f.write_char(match *dir {
+                        (0, 1) => '>',
+                        (0, -1) => '<',
+                        (-1, 0) => '^',
+                        _ => 'v'
+                    })?;
+                } else {
+                    write!(f, "{}", self.track[(y, x)])?;
+                }
+            }
+            f.write_char('\n')?;
         }
+        Ok(())
     }
 }
 
-fn solve_part2(mut scenario: Scenario) -> (usize, usize) {
-    loop {
-        do_tick(&mut scenario);
-        if scenario.carts.is_empty() {
-            panic!("No carts remaining");
-        }
-        if scenario.carts.len() == 1 {
-            break scenario.carts.remove(0).pos;
-        }
+impl Scenario {
+    /// Replace the policy that decides how carts turn at intersections
+    fn set_policy(&mut self, policy: TurnPolicy) {
+        self.policy = policy;
     }
-}
 
-fn do_tick(scenario: &mut Scenario) -> Option<(usize, usize)> {
-    // Sort the carts by evaluation order, which is y, x order
-    scenario.carts.sort_by_key(|cart| cart.pos);
+    /// Move every cart once, in reading order. Carts that crash are removed immediately, so carts
+    /// that still have to move can no longer run into them. Returns the crashes in the order in
+    /// which they happened.
+    fn do_tick(&mut self) -> Vec<Crash> {
+        self.tick += 1;
+        self.carts.sort_by_key(|cart| cart.pos);
+
+        let mut crashes = vec![];
+        let mut crashed = vec![false; self.carts.len()];
+
+        for i in 0..self.carts.len() {
+            if crashed[i] {
+                // Something already crashed into this cart before it could move
+                continue;
+            }
 
-    // Record the starting positions of the carts along with their sort order. After performing the
-    // step, we need this to detect crashes.
-    let start_positions: HashMap<_, _> = scenario.carts.iter().enumerate().map(|(i, cart)| (cart.pos, i)).collect();
+            self.move_cart(i);
+
+            let pos = self.carts[i].pos;
+            let other = (0..self.carts.len())
+                .find(|&j| j != i && !crashed[j] && self.carts[j].pos == pos);
+
+            if let Some(j) = other {
+                crashed[i] = true;
+                crashed[j] = true;
+                crashes.push(Crash {
+                    tick: self.tick,
+                    pos,
+                    carts: [self.carts[i].id, self.carts[j].id],
+                });
+            }
+        }
+
+        let mut crashed = crashed.into_iter();
+        self.carts.retain(|_| !crashed.next().unwrap());
+        self.crash_sites = crashes.iter().map(|c| c.pos).collect();
+
+        crashes
+    }
+
+    fn move_cart(&mut self, i: usize) {
+        let cart = &mut self.carts[i];
 
-    // Perform cart actions
-    for cart in &mut scenario.carts {
         // Move one step in the current direction
         cart.pos = (
             (cart.pos.0 as i32 + cart.dir.0) as usize,
@@ -101,71 +234,51 @@ fn do_tick(scenario: &mut Scenario) -> Option<(usize, usize)> {
         );
 
         // Check current track type and act if necessary
-        match scenario.track[cart.pos] {
+        match self.track[cart.pos] {
             TrackCell::Straight => {}
             TrackCell::Curve { sign } => cart.dir = (sign * cart.dir.1, sign * cart.dir.0),
             TrackCell::Intersection => {
-                match cart.intersection_count % 3 {
-                    0 => Some(-1),
-                    1 => None,
-                    2 => Some(1),
-                    _ => panic!()
-                }.map(|sign| {
-                    let sign = sign * if cart.dir.0 == 0 { 1 } else { -1 };
-                    cart.dir = (sign * cart.dir.1, sign * cart.dir.0);
-                });
-
+                let (dy, dx) = cart.dir;
+                cart.dir = match (self.policy)(cart.id, cart.intersection_count) {
+                    Turn::Left => (-dx, dy),
+                    Turn::Straight => (dy, dx),
+                    Turn::Right => (dx, -dy),
+                };
                 cart.intersection_count += 1;
             }
             _ => panic!("Invalid state")
         };
     }
+}
 
-    // Crash detection
-    let mut end_positions: HashMap<(usize, usize), _> = HashMap::with_capacity(scenario.carts.len());
-
-    let mut crashes = vec![];
-    let mut crashed_carts = HashSet::new();
-
-    for (i, cart) in scenario.carts.iter().enumerate() {
-        if crashed_carts.contains(&i) {
-            // Something already crashed into this cart so we consider it to not have moved
-            continue;
-        }
-
-        let mut crashed_with = None;
-
-        if end_positions.contains_key(&cart.pos) {
-            // This cart ends at the same position as another one, so it crashed
-            crashed_with = Some(end_positions[&cart.pos]);
+fn solve_part1(mut scenario: Scenario) -> Coord {
+    loop {
+        if let Some(crash) = scenario.do_tick().first() {
+            break crash.pos;
         }
+    }
+}
 
-        end_positions.insert(cart.pos, i);
-
-        if start_positions.contains_key(&cart.pos) {
-            // Another cart started at our end position. If that cart would have moved after the 
-            // current cart did, that was a crash.
-            if start_positions[&cart.pos] > i {
-                crashed_with = Some(start_positions[&cart.pos]);
-            }
+fn solve_part2(mut scenario: Scenario) -> Coord {
+    loop {
+        scenario.do_tick();
+        if scenario.carts.is_empty() {
+            panic!("No carts remaining");
         }
-
-        if let Some(other_i) = crashed_with {
-            crashed_carts.insert(i);
-            crashed_carts.insert(other_i);
-
-            crashes.push(cart.pos.clone());
+        if scenario.carts.len() == 1 {
+            break scenario.carts.remove(0).pos;
         }
     }
+}
 
-    // Filter out the crashed carts
-    let mut filter_indices: Vec<usize> = crashed_carts.into_iter().collect();
-    filter_indices.sort();
-    for (removed, i) in filter_indices.into_iter().enumerate() {
-        scenario.carts.remove(i - removed);
+/// Run the carts until at most one of them is left, or until the tick limit in case the carts
+/// never crash, and list every crash in chronological order
+fn crash_log(mut scenario: Scenario, max_ticks: usize) -> Vec<Crash> {
+    let mut log = vec![];
+    while scenario.carts.len() > 1 && scenario.tick < max_ticks {
+        log.extend(scenario.do_tick());
     }
-
-    if crashes.is_empty() { None } else { Some(crashes.remove(0)) }
+    log
 }
 
 fn get_puzzle_input() -> Scenario {
@@ -175,7 +288,7 @@ fn get_puzzle_input() -> Scenario {
 fn parse_input(input: String) -> Scenario {
     let lines: Vec<_> = non_empty_lines(input)
         .into_iter()
-        .map(|line| String::from(line.trim_right()))
+        .map(|line| String::from(line.trim_end()))
         .collect();
 
     // Determine the width of the grid by checking the longest trimmed input line
@@ -198,7 +311,8 @@ fn parse_input(input: String) -> Scenario {
 
             match cell {
                 GridCell::Cart(vy, vx) => {
-                    carts.push(Cart { pos: (y, x), dir: (vy, vx), intersection_count: 0 });
+                    let id = carts.len();
+                    carts.push(Cart { id, pos: (y, x), dir: (vy, vx), intersection_count: 0 });
                     grid[(y, x)] = TrackCell::Straight
                 }
                 GridCell::Track(t) => grid[(y, x)] = match t {
@@ -213,7 +327,13 @@ fn parse_input(input: String) -> Scenario {
         }
     }
 
-    Scenario { track: grid, carts }
+    Scenario {
+        track: grid,
+        carts,
+        policy: cycle_turns(vec![Turn::Left, Turn::Straight, Turn::Right]),
+        tick: 0,
+        crash_sites: vec![],
+    }
 }
 
 #[cfg(test)]
@@ -250,14 +370,66 @@ mod test {
   \....../   ");
 
         let expected_carts = vec![
-            Cart { pos: (0, 2), dir: (0, 1), intersection_count: 0 },
-            Cart { pos: (3, 9), dir: (1, 0), intersection_count: 0 },
+            Cart { id: 0, pos: (0, 2), dir: (0, 1), intersection_count: 0 },
+            Cart { id: 1, pos: (3, 9), dir: (1, 0), intersection_count: 0 },
         ];
 
         assert_eq!(expected_grid.trim(), scenario.track.format_dense().unwrap().trim());
         assert_eq!(expected_carts, scenario.carts);
     }
 
+    #[test]
+    fn test_crash_log() {
+        let log = crash_log(parse_input(get_test_input_part2()), MAX_TICKS);
+        assert_eq!(log, vec![
+            Crash { tick: 1, pos: (0, 2), carts: [1, 0] },
+            Crash { tick: 1, pos: (4, 2), carts: [5, 4] },
+            Crash { tick: 1, pos: (4, 6), carts: [6, 3] },
+            Crash { tick: 3, pos: (4, 2), carts: [7, 2] },
+        ]);
+
+        // Two carts that chase each other around a loop never crash
+        let scenario = parse_input(String::from("/>-\\\n|  |\n\\-</"));
+        assert_eq!(crash_log(scenario, 1000), vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "A turn cycle needs at least one turn")]
+    fn test_empty_turn_cycle() {
+        let _ = cycle_turns(vec![]);
+    }
+
+    #[test]
+    fn test_turn_policy() {
+        // The second cart reaches an intersection in the first tick, where it would turn left
+        let mut scenario = parse_input(get_test_input());
+        scenario.do_tick();
+        assert_eq!(scenario.carts[1].dir, (0, 1));
+
+        let mut scenario = parse_input(get_test_input());
+        scenario.set_policy(Box::new(|id, _| if id == 1 { Turn::Straight } else { Turn::Left }));
+        scenario.do_tick();
+        assert_eq!(scenario.carts[1].dir, (1, 0));
+    }
+
+    #[test]
+    fn test_render() {
+        let mut scenario = parse_input(get_test_input_part2());
+        scenario.do_tick();
+
+        let expected = String::from(r"
+/.X.\
+.   .
+. v.+.\
+. . . .
+\.X./ X
+  .   .
+  ^...^");
+
+        let rendered: Vec<_> = scenario.to_string().lines().map(|l| l.trim_end().to_string()).collect();
+        assert_eq!(expected.trim_start(), rendered.join("\n"));
+    }
+
     fn get_test_input() -> String {
         return String::from(r"
 /->-\        
@@ -1,15 +1,18 @@
 //! Solutions for https://adventofcode.com/2018/day/15
-use std;
-use std::collections::HashSet;
 use std::fmt::Display;
 use std::fmt::Error;
 use std::fmt::Formatter;
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::Write;
 use std::mem::swap;
+use std::thread;
 
 use days::day15::Tile::ActorRef;
 use utils::data::load_data;
 use utils::data::non_empty_lines;
 use utils::matrix::Matrix;
+use utils::search::bfs;
 
 #[allow(dead_code)]
 pub fn part1() {
@@ -21,231 +24,584 @@ pub fn part2() {
     println!("{}", solve_part2(get_puzzle_input()));
 }
 
-const ADJACENT_OFFSETS: [(i32, i32); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+/// Print every elf attack power that was tried for part 2, both with a search and a parallel sweep
+#[allow(dead_code)]
+pub fn report_part2() {
+    let battle = get_puzzle_input();
 
-type Grid = Matrix<Tile>;
+    let search = search_elf_ap(&battle, MAX_ELF_AP);
+    println!("Search:");
+    for trial in &search.trials {
+        println!("{}", trial);
+    }
+    println!("Monotonic: {}\n", search.is_monotonic());
 
-#[derive(Clone)]
-struct World {
-    grid: Grid,
-    actors: Vec<Actor>,
-    elf_ap: i16,
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let sweep = sweep_elf_ap(&battle, MAX_ELF_AP, threads);
+    println!("Sweep over {} threads:", threads);
+    for trial in &sweep.trials {
+        println!("{}", trial);
+    }
+
+    println!("\nBest: {:?}, sweep agrees: {}", search.best(), search.best() == sweep.best());
 }
 
+/// Print everything that happens during the part 1 battle, after listing the actors that the
+/// events refer to
+#[allow(dead_code)]
+pub fn log_part1() {
+    let mut battle = get_puzzle_input();
+    for (i, actor) in battle.actors().iter().enumerate() {
+        println!("Actor {}: {} at {},{}", i, actor.clan.0, actor.loc.1, actor.loc.0);
+    }
+    while !battle.is_over() {
+        for event in battle.step_turn() {
+            println!("{}", event);
+        }
+    }
+    println!("Outcome: {}", battle.outcome());
+}
+
+/// Write a round by round replay of the part 1 battle to a file
+#[allow(dead_code)]
+pub fn replay_part1() {
+    let path = "day15_replay.txt";
+    let mut file = File::create(path).unwrap();
+    file.write_all(replay(get_puzzle_input()).as_bytes()).unwrap();
+    println!("Replay written to {}", path);
+}
+
+const ADJACENT_OFFSETS: [(i32, i32); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+
+type Coord = (usize, usize);
+
+type Grid = Matrix<Tile>;
+
 #[derive(Copy, Clone)]
 enum Tile {
     Empty,
     Wall,
-    ActorRef(usize),
+    ActorRef(usize, Clan),
 }
 
 impl Display for Tile {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
-        let s = match self {
-            Tile::Empty => ".".to_owned(),
-            Tile::Wall => "#".to_owned(),
-            Tile::ActorRef(i) => i.to_string()
+        let c = match self {
+            Tile::Empty => '.',
+            Tile::Wall => '#',
+            Tile::ActorRef(_, clan) => clan.0
         };
-        f.write_str(&s)
+        f.write_char(c)
     }
 }
 
-#[derive(Debug, Clone)]
-struct Actor {
-    clan: Clan,
-    loc: (usize, usize),
-    hp: i16,
+/// A clan is identified by the letter that represents its units on the map
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Clan(pub char);
+
+pub const ELF: Clan = Clan('E');
+pub const GOBLIN: Clan = Clan('G');
+
+impl Clan {
+    pub fn name(&self) -> String {
+        match *self {
+            ELF => "Elves".to_owned(),
+            GOBLIN => "Goblins".to_owned(),
+            Clan(c) => format!("Clan {}", c)
+        }
+    }
 }
 
-#[derive(Eq, PartialEq, Debug, Clone)]
-enum Clan {
-    Elf,
-    Goblin,
+/// Hit points and attack power
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Stats {
+    pub hp: i16,
+    pub ap: i16,
 }
 
-struct BattleResult {
-    checksum: u32,
-    dead_elfs: usize,
+pub const DEFAULT_STATS: Stats = Stats { hp: 200, ap: 3 };
+
+#[derive(Debug, Clone)]
+pub struct Actor {
+    pub clan: Clan,
+    pub loc: Coord,
+    pub hp: i16,
+    pub ap: i16,
 }
 
-fn solve_part1(world: World) -> u32 {
-    perform_battle(world).checksum
+impl Actor {
+    pub fn is_alive(&self) -> bool {
+        self.hp > 0
+    }
 }
 
-fn solve_part2(mut world: World) -> u32 {
-    loop {
-        let result = perform_battle(world.clone());
+/// Something that happened during a battle. Actors are referred to by their index.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Event {
+    Move { actor: usize, from: Coord, to: Coord },
+    Attack { actor: usize, target: usize, damage: i16, hp_left: i16 },
+    Death { actor: usize },
+    RoundEnd { full_rounds: usize },
+    BattleEnd { full_rounds: usize, winner: Clan },
+}
 
-        if result.dead_elfs == 0 {
-            break result.checksum;
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self {
+            Event::Move { actor, from, to } => write!(
+                f, "Actor {} moves from {},{} to {},{}", actor, from.1, from.0, to.1, to.0
+            ),
+            Event::Attack { actor, target, damage, hp_left } => write!(
+                f, "Actor {} hits actor {} for {}, leaving {} HP", actor, target, damage, hp_left
+            ),
+            Event::Death { actor } => write!(f, "Actor {} dies", actor),
+            Event::RoundEnd { full_rounds } => write!(f, "Round {} ends", full_rounds),
+            Event::BattleEnd { full_rounds, winner } => write!(
+                f, "Battle ends after {} full rounds, clan {} wins", full_rounds, winner.0
+            ),
         }
+    }
+}
 
-        world.elf_ap += 1;
+/// Renders the map in the puzzle's notation, followed by the hit points of the actors on every row
+impl Display for Battle {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        for (y, row) in self.grid.rows().enumerate() {
+            let line: String = row.iter().map(|t| t.to_string()).collect();
+
+            let actors: Vec<_> = row.iter()
+                .filter_map(|t| match t {
+                    Tile::ActorRef(a, clan) => Some(format!("{}({})", clan.0, self.actors[*a].hp)),
+                    _ => None
+                })
+                .collect();
+
+            if actors.is_empty() {
+                f.write_str(&line)?;
+            } else {
+                write!(f, "{}   {}", line, actors.join(", "))?;
+            }
+            if y < self.grid.height - 1 {
+                f.write_char('\n')?;
+            }
+        }
+        Ok(())
     }
 }
 
-fn perform_battle(mut world: World) -> BattleResult {
-    let mut full_rounds = 0;
-    loop {
-        let result = perform_round(world);
-        world = result.0;
+/// A battle that can be stepped through one round or one actor turn at a time
+#[derive(Clone)]
+pub struct Battle {
+    grid: Grid,
+    actors: Vec<Actor>,
+    // Actors that still have to take their turn in the current round, last one first
+    pending_turns: Vec<usize>,
+    full_rounds: usize,
+    winner: Option<Clan>,
+}
 
-        if result.1 {
-            // Battle ended
-            break;
-        }
-        full_rounds += 1;
+impl Battle {
+    pub fn full_rounds(&self) -> usize {
+        self.full_rounds
     }
 
-    let remaining_hp = world.actors.iter()
-        .map(|a| a.hp.max(0) as u32)
-        .sum::<u32>();
+    pub fn winner(&self) -> Option<Clan> {
+        self.winner
+    }
 
-    let dead_elfs = world.actors.iter()
-        .filter(|a| a.clan == Clan::Elf)
-        .filter(|a| a.hp <= 0)
-        .count();
+    pub fn is_over(&self) -> bool {
+        self.winner.is_some()
+    }
 
-    BattleResult {
-        checksum: remaining_hp * full_rounds,
-        dead_elfs,
+    /// Get all actors, including dead ones, in the order that events refer to them by
+    pub fn actors(&self) -> &[Actor] {
+        &self.actors
     }
-}
 
-fn shift_loc(loc: (usize, usize), shift: (i32, i32)) -> (usize, usize) {
-    ((loc.0 as i32 + shift.0) as usize, (loc.1 as i32 + shift.1) as usize)
-}
+    /// Set the hit points and attack power of every actor of a clan
+    #[allow(dead_code)]
+    pub fn set_clan_stats(&mut self, clan: Clan, stats: Stats) {
+        for i in 0..self.actors.len() {
+            if self.actors[i].clan == clan {
+                self.set_actor_stats(i, stats);
+            }
+        }
+    }
 
-fn perform_round(world: World) -> (World, bool) {
-    let World { mut grid, mut actors, elf_ap } = world;
+    /// Set the attack power of every actor of a clan, keeping their hit points
+    pub fn set_clan_ap(&mut self, clan: Clan, ap: i16) {
+        for actor in self.actors.iter_mut().filter(|a| a.clan == clan) {
+            actor.ap = ap;
+        }
+    }
 
-    // Determine the actor ordering
-    let mut actor_order: Vec<_> = actors.iter().enumerate()
-        .map(|(i, a)| (i, a.loc))
-        .collect();
+    /// Set the hit points and attack power of a single actor
+    #[allow(dead_code)]
+    pub fn set_actor_stats(&mut self, actor: usize, stats: Stats) {
+        self.actors[actor].hp = stats.hp;
+        self.actors[actor].ap = stats.ap;
+    }
 
-    actor_order.sort_by_key(|tup| tup.1);
-    let actor_order: Vec<_> = actor_order.into_iter().map(|tup| tup.0).collect();
+    /// Count the dead actors of a clan
+    pub fn deaths(&self, clan: Clan) -> usize {
+        self.actors.iter()
+            .filter(|a| a.clan == clan && !a.is_alive())
+            .count()
+    }
 
-    let mut battle_ended = false;
+    /// The number of full rounds times the total hit points that are left
+    pub fn outcome(&self) -> u32 {
+        let remaining_hp = self.actors.iter()
+            .map(|a| a.hp.max(0) as u32)
+            .sum::<u32>();
 
-    // Let every actor perform their actions, if not dead
-    for i in actor_order.into_iter() {
-        if actors[i].hp > 0 {
-            // Check if there's any enemies remaining at all, if not the battle has ended
-            let mut any_enemies = false;
-            for j in 0..actors.len() {
-                if actors[j].clan != actors[i].clan && actors[j].hp > 0 {
-                    any_enemies = true;
-                    break;
-                }
-            }
-            if !any_enemies {
-                battle_ended = true;
+        remaining_hp * self.full_rounds as u32
+    }
+
+    /// Fight until a single clan remains and return the outcome
+    pub fn run(&mut self) -> u32 {
+        while !self.is_over() {
+            self.step_round();
+        }
+        self.outcome()
+    }
+
+    /// Let the remaining actors of the current round take their turns
+    pub fn step_round(&mut self) -> Vec<Event> {
+        let mut events = vec![];
+        while !self.is_over() {
+            let turn = self.step_turn();
+            let round_ended = matches!(turn.last(), Some(Event::RoundEnd { .. }));
+            events.extend(turn);
+            if round_ended {
                 break;
             }
+        }
+        events
+    }
 
-            let mut enemies_in_range = get_enemies_in_range(&actors, i, &grid);
+    /// Let the next actor take its turn
+    pub fn step_turn(&mut self) -> Vec<Event> {
+        let mut events = vec![];
+        if self.is_over() {
+            return events;
+        }
 
-            if enemies_in_range.is_empty() {
-                // No enemy in range, so we need to determine the best step, if any
-                let mut distances = Matrix::new(grid.height, grid.width, std::u32::MAX);
-                let mut initial_dirs = Matrix::new(grid.height, grid.width, 4usize);
-                for (o, offset) in ADJACENT_OFFSETS.iter().enumerate() {
-                    initial_dirs[shift_loc(actors[i].loc, *offset)] = o;
-                }
-                distances[actors[i].loc] = 0;
-                let mut frontier = vec![actors[i].loc];
-
-                let mut dir_to_target = None;
-
-                while dir_to_target.is_none() && !frontier.is_empty() {
-                    // For every entry in the frontier, find cells that have a higher distance than
-                    // the next distance. 
-                    let mut next_frontier = HashSet::new();
-
-                    for origin in &frontier {
-                        let next_dist = distances[*origin] + 1;
-                        let cur_dir = initial_dirs[*origin];
-
-                        for offset in &ADJACENT_OFFSETS {
-                            let loc = shift_loc(*origin, *offset);
-
-                            match grid[loc] {
-                                Tile::Empty => {
-                                    if distances[loc] > next_dist || (distances[loc] == next_dist && cur_dir < initial_dirs[loc]) {
-                                        // We found a faster way to get here, or a way that starts
-                                        // from a favorable initial move.
-                                        distances[loc] = next_dist;
-                                        initial_dirs[loc] = initial_dirs[loc].min(cur_dir);
-                                        next_frontier.insert(loc);
-                                    }
-                                }
-                                Tile::ActorRef(a) => {
-                                    if actors[a].clan != actors[i].clan {
-                                        dir_to_target = match dir_to_target {
-                                            None => Some(cur_dir),
-                                            Some(old) => Some(old.min(cur_dir))
-                                        };
-                                    }
-                                }
-                                Tile::Wall => {}
-                            }
-                        }
-                    }
-
-                    frontier = next_frontier.into_iter().collect();
-                }
+        if self.pending_turns.is_empty() {
+            // Start a new round, where the actors take turns in reading order of their locations
+            let mut order: Vec<_> = (0..self.actors.len())
+                .filter(|i| self.actors[*i].is_alive())
+                .collect();
+            order.sort_by_key(|i| self.actors[*i].loc);
+            order.reverse();
+            self.pending_turns = order;
+        }
 
-                if let Some(dir) = dir_to_target {
-                    // Found a way to get to a target, let's move!
-                    let next_loc = shift_loc(actors[i].loc, ADJACENT_OFFSETS[dir]);
-                    let mut actor_ref = Tile::Empty;
-                    swap(&mut grid[actors[i].loc], &mut actor_ref);
-                    grid[next_loc] = actor_ref;
+        let i = self.pending_turns.pop().unwrap();
+        let clan = self.actors[i].clan;
 
-                    actors[i].loc = next_loc;
+        // Check if there's any enemies remaining at all, if not the battle has ended
+        if !self.actors.iter().any(|a| a.clan != clan && a.is_alive()) {
+            self.pending_turns.clear();
+            self.winner = Some(clan);
+            events.push(Event::BattleEnd { full_rounds: self.full_rounds, winner: clan });
+            return events;
+        }
 
-                    // Update the enemies in range
-                    enemies_in_range = get_enemies_in_range(&actors, i, &grid);
-                }
+        let mut enemies_in_range = get_enemies_in_range(&self.actors, i, &self.grid);
+
+        if enemies_in_range.is_empty() {
+            // No enemy in range, so we need to determine the best step, if any
+            if let Some(next_loc) = get_step_towards_enemy(&self.actors, i, &self.grid) {
+                // Found a way to get to a target, let's move!
+                let from = self.actors[i].loc;
+                let mut actor_ref = Tile::Empty;
+                swap(&mut self.grid[from], &mut actor_ref);
+                self.grid[next_loc] = actor_ref;
+
+                self.actors[i].loc = next_loc;
+                events.push(Event::Move { actor: i, from, to: next_loc });
+
+                // Update the enemies in range
+                enemies_in_range = get_enemies_in_range(&self.actors, i, &self.grid);
             }
+        }
 
-            if !enemies_in_range.is_empty() {
-                // Fight!
-                // Filter the enemies in range that have the lowest hp
-                let min_hp = enemies_in_range.iter()
-                    .map(|a| actors[*a].hp)
-                    .min().unwrap();
+        if !enemies_in_range.is_empty() {
+            // Fight!
+            // Fight the enemy in range that has the lowest hp. The enemies are already sorted by
+            // reading order, and min_by_key returns the first minimum.
+            let enemy_i = *enemies_in_range.iter()
+                .min_by_key(|a| self.actors[**a].hp)
+                .unwrap();
+
+            let damage = self.actors[i].ap;
+            self.actors[enemy_i].hp -= damage;
+            events.push(Event::Attack {
+                actor: i,
+                target: enemy_i,
+                damage,
+                hp_left: self.actors[enemy_i].hp.max(0),
+            });
+
+            if !self.actors[enemy_i].is_alive() {
+                // He ded, remove from grid
+                self.grid[self.actors[enemy_i].loc] = Tile::Empty;
+                events.push(Event::Death { actor: enemy_i });
+            }
+        }
 
-                enemies_in_range = enemies_in_range.into_iter()
-                    .filter(|a| actors[*a].hp == min_hp)
-                    .collect();
+        // Dead actors don't get to take their turn anymore
+        let actors = &self.actors;
+        self.pending_turns.retain(|a| actors[*a].is_alive());
 
-                // Fight the first enemy (already sorted by reading order)
-                let enemy_i = enemies_in_range[0];
+        if self.pending_turns.is_empty() {
+            self.full_rounds += 1;
+            events.push(Event::RoundEnd { full_rounds: self.full_rounds });
+        }
 
-                let ap = if actors[i].clan == Clan::Elf { elf_ap } else { 3 };
-                actors[enemy_i].hp -= ap;
+        events
+    }
+}
 
-                if actors[enemy_i].hp <= 0 {
-                    // He ded, remove from grid
-                    grid[actors[enemy_i].loc] = Tile::Empty;
-                }
+/// Fight a battle and render it after every round, in the format of the puzzle examples
+fn replay(mut battle: Battle) -> String {
+    let mut result = format!("Initially:\n{}\n\n", battle);
+
+    while !battle.is_over() {
+        let events = battle.step_round();
+        let acted = events.iter().any(|e| matches!(e, Event::Move { .. } | Event::Attack { .. }));
+
+        if let Some(Event::RoundEnd { full_rounds }) = events.last() {
+            let plural = if *full_rounds == 1 { "" } else { "s" };
+            result += &format!("After {} round{}:\n{}\n\n", full_rounds, plural, battle);
+        } else if acted {
+            result += &format!(
+                "During round {}, when combat ends:\n{}\n\n", battle.full_rounds() + 1, battle
+            );
+        }
+    }
+
+    let winner = battle.winner().unwrap();
+    let remaining_hp: i16 = battle.actors.iter().map(|a| a.hp.max(0)).sum();
+    result += &format!(
+        "Combat ends after {} full rounds\n{} win with {} total hit points left\nOutcome: {} * {} = {}\n",
+        battle.full_rounds(),
+        winner.name(),
+        remaining_hp,
+        battle.full_rounds(),
+        remaining_hp,
+        battle.outcome()
+    );
+    result
+}
+
+fn solve_part1(mut battle: Battle) -> u32 {
+    battle.run()
+}
+
+fn solve_part2(battle: Battle) -> u32 {
+    search_elf_ap(&battle, MAX_ELF_AP).best().unwrap().1
+}
+
+/// The attack power at which every hit kills, so more would never help
+const MAX_ELF_AP: i16 = DEFAULT_STATS.hp;
+
+/// The result of fighting a battle with a certain elf attack power
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Trial {
+    pub elf_ap: i16,
+    pub result: TrialResult,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TrialResult {
+    /// An elf died, at which point the battle was stopped
+    ElfDied { full_rounds: usize },
+    /// The elves won without losing anyone
+    Flawless { outcome: u32 },
+}
+
+impl Display for Trial {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self.result {
+            TrialResult::ElfDied { full_rounds } => write!(
+                f, "Attack power {}: an elf died after {} full rounds", self.elf_ap, full_rounds
+            ),
+            TrialResult::Flawless { outcome } => write!(
+                f, "Attack power {}: no losses, outcome {}", self.elf_ap, outcome
+            ),
+        }
+    }
+}
+
+/// All trials of a search for the lowest elf attack power, in the order they were tried
+pub struct ApSearch {
+    pub trials: Vec<Trial>,
+}
+
+impl ApSearch {
+    /// Get the lowest attack power that was tried without losses, along with its outcome
+    pub fn best(&self) -> Option<(i16, u32)> {
+        self.trials.iter()
+            .filter_map(|t| match t.result {
+                TrialResult::Flawless { outcome } => Some((t.elf_ap, outcome)),
+                _ => None
+            })
+            .min()
+    }
+
+    /// Check that no trial where an elf died had a higher attack power than a flawless one, which
+    /// is what a binary search relies on
+    pub fn is_monotonic(&self) -> bool {
+        let max_failed = self.trials.iter()
+            .filter(|t| !t.is_flawless())
+            .map(|t| t.elf_ap)
+            .max();
+
+        match (max_failed, self.best()) {
+            (Some(failed), Some((best, _))) => failed < best,
+            _ => true
+        }
+    }
+}
+
+impl Trial {
+    fn is_flawless(&self) -> bool {
+        matches!(self.result, TrialResult::Flawless { .. })
+    }
+}
+
+/// Fight a battle with the given elf attack power, stopping as soon as an elf dies. The elves keep
+/// whatever hit points the battle was set up with.
+fn try_elf_ap(battle: &Battle, elf_ap: i16) -> Trial {
+    let mut battle = battle.clone();
+    battle.set_clan_ap(ELF, elf_ap);
+
+    while !battle.is_over() {
+        let events = battle.step_turn();
+        let any_deaths = events.iter().any(|e| matches!(e, Event::Death { .. }));
+        if any_deaths && battle.deaths(ELF) > 0 {
+            let result = TrialResult::ElfDied { full_rounds: battle.full_rounds() };
+            return Trial { elf_ap, result };
+        }
+    }
+
+    Trial { elf_ap, result: TrialResult::Flawless { outcome: battle.outcome() } }
+}
+
+/// Find the lowest elf attack power for which no elf dies, assuming that more attack power never
+/// hurts. The attack power is increased in growing steps until a flawless victory is found, after
+/// which a binary search narrows down the boundary.
+pub fn search_elf_ap(battle: &Battle, max_ap: i16) -> ApSearch {
+    let mut trials = vec![];
+
+    // Everything below lo is known to fail, hi is known to succeed
+    let mut lo = DEFAULT_STATS.ap;
+    let mut hi = None;
+
+    let mut ap = lo;
+    let mut step = 1;
+    while ap <= max_ap {
+        let trial = try_elf_ap(battle, ap);
+        trials.push(trial);
+        if trial.is_flawless() {
+            hi = Some(ap);
+            break;
+        }
+        lo = ap + 1;
+        ap = if ap == max_ap { max_ap + 1 } else { (ap + step).min(max_ap) };
+        step *= 2;
+    }
+
+    if let Some(mut hi) = hi {
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let trial = try_elf_ap(battle, mid);
+            trials.push(trial);
+            if trial.is_flawless() {
+                hi = mid;
+            } else {
+                lo = mid + 1;
             }
         }
     }
 
-    (World { grid, actors, elf_ap }, battle_ended)
+    ApSearch { trials }
 }
 
-fn get_enemies_in_range(actors: &Vec<Actor>, actor_i: usize, grid: &Grid) -> Vec<(usize)> {
+/// Try every elf attack power from the lowest up, running a batch of battles in parallel at a
+/// time, until a flawless victory is found. Does not rely on monotonicity.
+pub fn sweep_elf_ap(battle: &Battle, max_ap: i16, threads: usize) -> ApSearch {
+    let mut trials = vec![];
+    let mut ap = DEFAULT_STATS.ap;
+
+    while ap <= max_ap {
+        let batch: Vec<i16> = (ap..=max_ap).take(threads.max(1)).collect();
+
+        let results: Vec<Trial> = thread::scope(|scope| {
+            let handles: Vec<_> = batch.iter()
+                .map(|ap| {
+                    let ap = *ap;
+                    scope.spawn(move || try_elf_ap(battle, ap))
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        let found = results.iter().any(|t| t.is_flawless());
+        trials.extend(results);
+        if found {
+            break;
+        }
+        ap += batch.len() as i16;
+    }
+
+    ApSearch { trials }
+}
+
+fn shift_loc(loc: Coord, shift: (i32, i32)) -> Coord {
+    ((loc.0 as i32 + shift.0) as usize, (loc.1 as i32 + shift.1) as usize)
+}
+
+/// Determine the location to step to in order to get closer to the nearest enemy, if any enemy
+/// can be reached at all
+fn get_step_towards_enemy(actors: &[Actor], actor_i: usize, grid: &Grid) -> Option<Coord> {
+    let actor = &actors[actor_i];
+
+    let empty_neighbours = |loc: &Coord| {
+        ADJACENT_OFFSETS.iter()
+            .map(|offset| shift_loc(*loc, *offset))
+            .filter(|l| matches!(grid[*l], Tile::Empty))
+            .collect::<Vec<_>>()
+    };
+
+    // Find the squares in range of an enemy that are nearest to us, and pick the first one in
+    // reading order
+    let from_actor = bfs(actor.loc, &empty_neighbours);
+    let target = actors.iter()
+        .filter(|a| a.clan != actor.clan && a.is_alive())
+        .flat_map(|a| empty_neighbours(&a.loc))
+        .filter_map(|loc| from_actor.distance(&loc).map(|d| (d, loc)))
+        .min()?
+        .1;
+
+    // Now find which of our adjacent squares is nearest to that target, again using reading order
+    // to break ties
+    let from_target = bfs(target, &empty_neighbours);
+    empty_neighbours(&actor.loc).into_iter()
+        .filter_map(|loc| from_target.distance(&loc).map(|d| (d, loc)))
+        .min()
+        .map(|(_, loc)| loc)
+}
+
+fn get_enemies_in_range(actors: &[Actor], actor_i: usize, grid: &Grid) -> Vec<usize> {
     let actor = &actors[actor_i];
     let mut result = vec![];
 
     for offset in &ADJACENT_OFFSETS {
         let loc = shift_loc(actor.loc, *offset);
-        if let ActorRef(a) = grid[loc] {
+        if let ActorRef(a, _) = grid[loc] {
             if actors[a].clan != actor.clan {
                 result.push(a);
             }
@@ -254,11 +610,12 @@ fn get_enemies_in_range(actors: &Vec<Actor>, actor_i: usize, grid: &Grid) -> Vec
     result
 }
 
-fn get_puzzle_input() -> World {
+fn get_puzzle_input() -> Battle {
     parse_input(load_data("day15"))
 }
 
-fn parse_input(input: String) -> World {
+/// Parse a map where every letter other than E and G is a unit of another clan as well
+fn parse_input(input: String) -> Battle {
     let lines = non_empty_lines(input);
     let height = lines.len();
     let width = lines[0].len();
@@ -271,11 +628,11 @@ fn parse_input(input: String) -> World {
             let loc = (y, x);
             let tile = match cell {
                 '#' => Tile::Wall,
-                'E' | 'G' => {
+                'A'..='Z' => {
                     let a = actors.len();
-                    let clan = if cell == 'E' { Clan::Elf } else { Clan::Goblin };
-                    actors.push(Actor { clan, loc, hp: 200 });
-                    ActorRef(a)
+                    let Stats { hp, ap } = DEFAULT_STATS;
+                    actors.push(Actor { clan: Clan(cell), loc, hp, ap });
+                    ActorRef(a, Clan(cell))
                 }
                 _ => Tile::Empty
             };
@@ -283,7 +640,7 @@ fn parse_input(input: String) -> World {
         }
     }
 
-    World { grid, actors, elf_ap: 3 }
+    Battle { grid, actors, pending_turns: vec![], full_rounds: 0, winner: None }
 }
 
 #[cfg(test)]
@@ -325,4 +682,175 @@ mod test {
             4988
         );
     }
+
+    #[test]
+    fn test_elf_ap_search() {
+        let input = String::from(r"
+#######
+#.G...#
+#...EG#
+#.#.#G#
+#..G#E#
+#.....#
+#######
+");
+        let battle = parse_input(input);
+
+        let search = search_elf_ap(&battle, MAX_ELF_AP);
+        assert_eq!(search.best(), Some((15, 4988)));
+        assert!(search.is_monotonic());
+        assert!(search.trials.len() < 13);
+        assert_eq!(
+            search.trials[0],
+            Trial { elf_ap: 3, result: TrialResult::ElfDied { full_rounds: 22 } }
+        );
+
+        let sweep = sweep_elf_ap(&battle, MAX_ELF_AP, 4);
+        assert_eq!(sweep.best(), Some((15, 4988)));
+        assert_eq!(sweep.trials.len(), 16);
+        assert!(sweep.is_monotonic());
+    }
+
+    #[test]
+    fn test_replay() {
+        let input = String::from(r"
+#######
+#.G...#
+#...EG#
+#.#.#G#
+#..G#E#
+#.....#
+#######
+");
+        let replay = replay(parse_input(input));
+
+        assert!(replay.starts_with("\
+Initially:
+#######
+#.G...#   G(200)
+#...EG#   E(200), G(200)
+#.#.#G#   G(200)
+#..G#E#   G(200), E(200)
+#.....#
+#######
+
+After 1 round:
+#######
+#..G..#   G(200)
+#...EG#   E(197), G(197)
+#.#G#G#   G(200), G(197)
+#...#E#   E(197)
+#.....#
+#######
+"));
+
+        assert!(replay.ends_with("\
+After 47 rounds:
+#######
+#G....#   G(200)
+#.G...#   G(131)
+#.#.#G#   G(59)
+#...#.#
+#....G#   G(200)
+#######
+
+Combat ends after 47 full rounds
+Goblins win with 590 total hit points left
+Outcome: 47 * 590 = 27730
+"));
+    }
+
+    #[test]
+    fn test_steps() {
+        let mut battle = parse_input(String::from("#######\n#E..G.#\n#######"));
+
+        assert_eq!(
+            battle.step_turn(),
+            vec![Event::Move { actor: 0, from: (1, 1), to: (1, 2) }]
+        );
+        assert_eq!(
+            battle.step_round(),
+            vec![
+                Event::Move { actor: 1, from: (1, 4), to: (1, 3) },
+                Event::Attack { actor: 1, target: 0, damage: 3, hp_left: 197 },
+                Event::RoundEnd { full_rounds: 1 },
+            ]
+        );
+        assert_eq!(battle.full_rounds(), 1);
+        assert_eq!(battle.winner(), None);
+    }
+
+    #[test]
+    fn test_custom_stats() {
+        let mut battle = parse_input(String::from("#######\n#E..G.#\n#######"));
+        battle.set_clan_stats(ELF, Stats { hp: 10, ap: 5 });
+        battle.set_actor_stats(1, Stats { hp: 9, ap: 4 });
+
+        let events = battle.step_round();
+        assert_eq!(events[2], Event::Attack { actor: 1, target: 0, damage: 4, hp_left: 6 });
+
+        let events = battle.step_round();
+        assert_eq!(
+            events,
+            vec![
+                Event::Attack { actor: 0, target: 1, damage: 5, hp_left: 4 },
+                Event::Attack { actor: 1, target: 0, damage: 4, hp_left: 2 },
+                Event::RoundEnd { full_rounds: 2 },
+            ]
+        );
+
+        assert_eq!(battle.run(), 3 * 2);
+        assert_eq!(battle.winner(), Some(ELF));
+        assert_eq!(battle.deaths(GOBLIN), 1);
+        assert_eq!(battle.deaths(ELF), 0);
+    }
+
+    #[test]
+    fn test_elf_ap_search_keeps_hp() {
+        let input = String::from(r"
+#######
+#.G...#
+#...EG#
+#.#.#G#
+#..G#E#
+#.....#
+#######
+");
+        let mut battle = parse_input(input);
+        battle.set_clan_stats(ELF, Stats { hp: 1000, ap: DEFAULT_STATS.ap });
+
+        // With 200 HP the elves would need 15 attack power, tough elves win right away
+        let search = search_elf_ap(&battle, MAX_ELF_AP);
+        assert_eq!(search.best(), Some((3, 120960)));
+        assert_eq!(sweep_elf_ap(&battle, MAX_ELF_AP, 4).best(), search.best());
+    }
+
+    #[test]
+    fn test_actors() {
+        let mut battle = parse_input(String::from("#######\n#E..G.#\n#######"));
+        battle.set_actor_stats(1, Stats { hp: 9, ap: 4 });
+
+        let events = battle.step_round();
+        let actors = battle.actors();
+        assert_eq!(actors.len(), 2);
+        match events[1] {
+            Event::Move { actor, to, .. } => {
+                assert_eq!(actors[actor].clan, GOBLIN);
+                assert_eq!(actors[actor].loc, to);
+                assert_eq!(actors[actor].ap, 4);
+            }
+            _ => panic!("Expected a move, got {}", events[1])
+        }
+    }
+
+    #[test]
+    fn test_three_clans() {
+        let mut battle = parse_input(String::from("#####\n#EXG#\n#####"));
+        battle.run();
+
+        // X is surrounded by enemies and dies first. E was hit by X every round while G was not, so
+        // G wins the remaining fight.
+        assert_eq!(battle.deaths(Clan('X')), 1);
+        assert_eq!(battle.winner(), Some(GOBLIN));
+    }
 }
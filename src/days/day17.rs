@@ -1,4 +1,11 @@
 //! Solutions for https://adventofcode.com/2018/day/17
+use std::fmt::Display;
+use std::fmt::Error;
+use std::fmt::Formatter;
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::Write;
+
 use regex::Regex;
 
 use days::day17::Square::*;
@@ -16,11 +23,26 @@ pub fn part2() {
     println!("{}", solve_part2(get_puzzle_input()));
 }
 
+/// Write the final map as text and as an image, to check the simulation visually
+#[allow(dead_code)]
+pub fn export_part1() {
+    let world = fully_simulate(get_puzzle_input());
+
+    let path = "day17.txt";
+    let mut file = File::create(path).unwrap();
+    file.write_all(render(&world).as_bytes()).unwrap();
+    println!("Map written to {}", path);
+
+    let path = "day17.ppm";
+    let mut file = File::create(path).unwrap();
+    file.write_all(&render_ppm(&world)).unwrap();
+    println!("Image written to {}", path);
+}
+
 type Coord = (usize, usize);
 
 const SPRING_X: usize = 500;
 
-#[derive(Clone, Eq, PartialEq)]
 struct World {
     grid: Grid,
     offset: Coord,
@@ -36,6 +58,30 @@ enum Square {
     StreamingWater,
 }
 
+impl Display for Square {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        let c = match *self {
+            Sand => '.',
+            Clay => '#',
+            SettledWater => '~',
+            StreamingWater => '|',
+        };
+        f.write_char(c)
+    }
+}
+
+impl Square {
+    /// The color of the square in image exports, as RGB
+    fn color(self) -> [u8; 3] {
+        match self {
+            Sand => [236, 214, 160],
+            Clay => [120, 72, 40],
+            SettledWater => [24, 64, 200],
+            StreamingWater => [120, 200, 255],
+        }
+    }
+}
+
 #[derive(Eq, PartialEq, Debug)]
 struct Vein {
     y: Coord,
@@ -53,17 +99,68 @@ fn solve_part2(veins: Vec<Vein>) -> usize {
 fn fully_simulate(veins: Vec<Vein>) -> World {
     let mut world = build_world(veins);
 
-    // Let the source spawn streaming water right beneath it
+    // The spring is above the grid, so the water enters it at the top row
     let local_spring = (0, SPRING_X - world.offset.1);
-    world = project_stream_down(world, local_spring);
+    flow(&mut world.grid, local_spring);
+
+    world
+}
+
+/// Let water flow into a square and recursively follow it down and sideways, only visiting squares
+/// that the water actually reaches. Returns whether the square ends up blocking water from above,
+/// which is the case for clay and settled water.
+fn flow(grid: &mut Grid, coord: Coord) -> bool {
+    let (y, x) = coord;
+    if y >= grid.height {
+        // Falls off the bottom of the map
+        return false;
+    }
+
+    match grid[coord] {
+        Clay | SettledWater => return true,
+        // Already visited, and that water flowed away
+        StreamingWater => return false,
+        Sand => grid[coord] = StreamingWater
+    }
+
+    if !flow(grid, (y + 1, x)) {
+        return false;
+    }
+
+    // Blocked from below, so spread out to both sides
+    let (left, wall_left) = spread(grid, coord, -1);
+    let (right, wall_right) = spread(grid, coord, 1);
 
+    if wall_left && wall_right {
+        for x in left..=right {
+            grid[(y, x)] = SettledWater;
+        }
+        true
+    } else {
+        false
+    }
+}
+
+/// Let water spread sideways from a square that is blocked from below, until it hits clay or finds
+/// a place to fall down. Returns the last square that was reached and whether that was against clay.
+fn spread(grid: &mut Grid, coord: Coord, dir: i32) -> (usize, bool) {
+    let (y, mut x) = coord;
     loop {
-        let new_world = do_tick(world.clone());
-        if new_world == world {
-            // Nothing changed, so we're done
-            break world;
+        let next = x as i32 + dir;
+        if next < 0 || next >= grid.width as i32 {
+            return (x, false);
+        }
+        let next = next as usize;
+
+        if grid[(y, next)] == Clay {
+            return (x, true);
+        }
+
+        grid[(y, next)] = StreamingWater;
+        x = next;
+        if !flow(grid, (y + 1, x)) {
+            return (x, false);
         }
-        world = new_world;
     }
 }
 
@@ -100,87 +197,23 @@ fn build_world(veins: Vec<Vein>) -> World {
     World { grid, offset: (min_y, min_x) }
 }
 
-fn do_tick(mut world: World) -> World {
+/// Render the map in the puzzle's notation
+fn render(world: &World) -> String {
+    world.grid.format_dense().unwrap() + "\n"
+}
+
+/// Render the map as a binary PPM image with one pixel per square
+fn render_ppm(world: &World) -> Vec<u8> {
     let (height, width) = (world.grid.height, world.grid.width);
+    let mut image = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    image.reserve(height * width * 3);
 
-    // Step 1: project down the streaming water
     for y in 0..height {
         for x in 0..width {
-            let coord = (y, x);
-            if world.grid[coord] == StreamingWater {
-                world = project_stream_down(world, coord);
-            }
-        }
-    }
-
-    // Step 2: flood fill
-    for y in 0..height - 1 {
-        for x in 0..width {
-            let coord = (y, x);
-            if world.grid[coord] == StreamingWater {
-                let beneath = world.grid[(y + 1, x)];
-                if beneath == Clay || beneath == SettledWater {
-                    world = flood_fill(world, coord);
-                }
-            }
+            image.extend_from_slice(&world.grid[(y, x)].color());
         }
     }
-
-    world
-}
-
-fn project_stream_down(mut world: World, local_coord: Coord) -> World {
-    let (start_y, x) = local_coord;
-    let height = world.grid.height;
-    for y in start_y..height {
-        let coord = (y, x);
-        if world.grid[coord] != Sand && y > start_y {
-            break;
-        }
-        world.grid[coord] = StreamingWater;
-    }
-    world
-}
-
-fn flood_fill(mut world: World, local_coord: Coord) -> World {
-    let y = local_coord.0;
-    let (span_left, span_right, filler) = calc_flood_fill(&world, local_coord);
-
-    for x in span_left..=span_right {
-        world.grid[(y, x)] = filler;
-    }
-    world
-}
-
-fn calc_flood_fill(world: &World, local_coord: Coord) -> (usize, usize, Square) {
-    let y = local_coord.0;
-
-    let partial_fill = |dir: i32| {
-        let mut found_clay = false;
-        let mut x = local_coord.1;
-        let mut span = x;
-        while x > 0 && x < world.grid.width - 1 {
-            x = (x as i32 + dir) as usize;
-            if world.grid[(y, x)] == Clay {
-                // Found clay on the side
-                found_clay = true;
-                break;
-            }
-            span = x;
-            let beneath = world.grid[(y + 1, x)];
-            if beneath == Sand || beneath == StreamingWater {
-                // Found sand or streaming water beneath, so we can stop here
-                break;
-            }
-        }
-        (span, found_clay)
-    };
-
-    let (span_left, clay_left) = partial_fill(-1);
-    let (span_right, clay_right) = partial_fill(1);
-    let filler = if clay_left && clay_right { SettledWater } else { StreamingWater };
-
-    (span_left, span_right, filler)
+    image
 }
 
 fn get_puzzle_input() -> Vec<Vein> {
@@ -224,6 +257,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_render() {
+        let world = fully_simulate(parse_input(get_test_input()));
+
+        let expected = String::from(r"
+......|.....#.
+.#..#||||...#.
+.#..#~~#|.....
+.#..#~~#|.....
+.#~~~~~#|.....
+.#~~~~~#|.....
+.#######|.....
+........|.....
+...|||||||||..
+...|#~~~~~#|..
+...|#~~~~~#|..
+...|#~~~~~#|..
+...|#######|..
+");
+        assert_eq!(render(&world), expected.trim_start());
+
+        let image = render_ppm(&world);
+        assert!(image.starts_with(b"P6\n14 13\n255\n"));
+        assert_eq!(image.len(), 13 + 14 * 13 * 3);
+    }
+
     #[test]
     fn test_parse() {
         let expected = vec![
@@ -2,6 +2,10 @@
 use std::collections::HashMap;
 
 use day18::Acre::*;
+use utils::automaton::Automaton;
+use utils::automaton::Boundary;
+use utils::automaton::Neighbourhood;
+use utils::automaton::Shortcut;
 use utils::data::load_data;
 use utils::data::non_empty_lines;
 use utils::matrix::Matrix;
@@ -25,30 +29,20 @@ enum Acre {
 
 type Area = Matrix<Acre>;
 
-fn solve(mut area: Area, minutes: usize) -> usize {
-    let mut count_hist = vec![];
-
-    for m in 0..minutes {
-        area = do_step(area);
-
-        let counts = get_total_counts(&area);
-        count_hist.push(counts.clone());
-
-        // See if we can find these counts previously and detect a cycle.
-        for offset in 1..m {
-            if offset * 2 <= m {
-                if count_hist[m - offset] == counts && count_hist[m - offset * 2] == counts {
-                    // We found a cycle so we can shortcut from here.
-                    let remaining_minutes = minutes - m - 1;
-                    let o = remaining_minutes % offset;
-                    let counts = &count_hist[m - offset + o];
-                    return counts[&Trees] * counts[&Lumberyard];
-                }
-            }
-        }
-    }
+fn solve(area: Area, minutes: usize) -> usize {
+    let mut automaton = Automaton::new(
+        area,
+        Neighbourhood::moore(1),
+        Boundary::Constant(Open),
+        next_acre,
+    );
+
+    // The landscape eventually ends up in a repeating pattern, so we can shortcut from there.
+    automaton.advance(minutes, Shortcut::Cycle, |a| resource_value(a.grid()) as i64).value as usize
+}
 
-    let counts = count_hist.last().unwrap();
+fn resource_value(area: &Area) -> usize {
+    let counts = get_total_counts(area);
     counts[&Trees] * counts[&Lumberyard]
 }
 
@@ -62,37 +56,14 @@ fn get_total_counts(area: &Area) -> HashMap<Acre, usize> {
     counts
 }
 
-fn do_step(area: Area) -> Area {
-    let mut new = Matrix::new(area.height, area.width, Acre::Open);
-
-    for y in 0..area.height {
-        for x in 0..area.width {
-            let c = count_surrounding(&area, (y, x));
-
-            new[(y, x)] = match area[(y, x)] {
-                Open => if c[&Trees] >= 3 { Trees } else { Open },
-                Trees => if c[&Lumberyard] >= 3 { Lumberyard } else { Trees },
-                Lumberyard => if c[&Lumberyard] > 0 && c[&Trees] > 0 { Lumberyard } else { Open }
-            }
-        }
-    }
-    new
-}
+fn next_acre(acre: Acre, surrounding: &[Acre]) -> Acre {
+    let count = |t: Acre| surrounding.iter().filter(|a| **a == t).count();
 
-fn count_surrounding(area: &Area, pos: (usize, usize)) -> HashMap<Acre, usize> {
-    let mut count = hashmap!(Open => 0, Trees => 0, Lumberyard => 0);
-
-    for oy in -1..=1 {
-        for ox in -1..=1 {
-            if !(oy == 0 && ox == 0) {
-                let (y, x) = (pos.0 as i32 - oy, pos.1 as i32 - ox);
-                if y >= 0 && y < area.height as i32 && x >= 0 && x < area.width as i32 {
-                    *count.entry(area[(y as usize, x as usize)]).or_insert(0) += 1;
-                }
-            }
-        }
+    match acre {
+        Open => if count(Trees) >= 3 { Trees } else { Open },
+        Trees => if count(Lumberyard) >= 3 { Lumberyard } else { Trees },
+        Lumberyard => if count(Lumberyard) > 0 && count(Trees) > 0 { Lumberyard } else { Open }
     }
-    count
 }
 
 fn get_puzzle_input() -> Area {
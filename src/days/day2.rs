@@ -12,7 +12,30 @@ pub fn part1() {
 
 #[allow(dead_code)]
 pub fn part2() {
-    println!("{}", solve_part2(get_puzzle_input()));
+    match solve_part2(get_puzzle_input()) {
+        Some(common) => println!("{}", common),
+        None => println!("No IDs differ by exactly one character")
+    }
+}
+
+/// Print all pairs of IDs that are at most two edits apart
+#[allow(dead_code)]
+pub fn report_similar() {
+    let box_ids = get_puzzle_input();
+    for pair in find_similar(&box_ids, 2) {
+        println!(
+            "{} {} are {} edits apart",
+            box_ids[pair.a], box_ids[pair.b], pair.distance
+        );
+    }
+}
+
+/// Two IDs, as indices in the list of IDs, and their edit distance
+#[derive(Debug, Eq, PartialEq)]
+struct SimilarPair {
+    a: usize,
+    b: usize,
+    distance: usize,
 }
 
 fn solve_part1(box_ids: Vec<String>) -> u32 {
@@ -39,25 +62,182 @@ fn solve_part1(box_ids: Vec<String>) -> u32 {
     }
 }
 
-fn solve_part2(box_ids: Vec<String>) -> String {
-    for a in box_ids.iter() {
-        for b in box_ids.iter() {
-            // Count how many character positions have different letters
-            let delta: u32 = a.chars().zip(b.chars())
-                .map(|(ca, cb)| (ca != cb) as u32)
-                .sum();
+fn solve_part2(box_ids: Vec<String>) -> Option<String> {
+    find_one_apart(&box_ids).map(|(a, b)| common_letters(&box_ids[a], &box_ids[b]))
+}
+
+/// Get the characters that are the same at their respective positions
+fn common_letters(a: &str, b: &str) -> String {
+    a.chars().zip(b.chars())
+        .filter(|(ca, cb)| ca == cb)
+        .map(|(ca, _)| ca)
+        .collect()
+}
+
+/// The base of the polynomial hashes of IDs, which wrap around at 64 bits
+const HASH_BASE: u64 = 0x100_0000_01b3;
+
+/// Polynomial hashes of every prefix of an ID, from which the hash of the ID with some of its
+/// characters deleted can be put together without building that string
+struct PrefixHashes {
+    prefixes: Vec<u64>,
+    powers: Vec<u64>,
+}
+
+impl PrefixHashes {
+    fn new(id: &[char]) -> PrefixHashes {
+        let mut prefixes = vec![0u64; id.len() + 1];
+        let mut powers = vec![1u64; id.len() + 1];
+        for (i, c) in id.iter().enumerate() {
+            // Offset the characters by one, so that leading zeroes still change the hash
+            prefixes[i + 1] = prefixes[i].wrapping_mul(HASH_BASE).wrapping_add(*c as u64 + 1);
+            powers[i + 1] = powers[i].wrapping_mul(HASH_BASE);
+        }
+        PrefixHashes { prefixes, powers }
+    }
 
-            if delta == 1 {
-                // Return the characters that are the same at their respective positions
-                return a.chars().zip(b.chars())
-                    .filter(|(ca, cb)| ca == cb)
-                    .map(|(ca, _)| ca)
-                    .collect();
+    /// Get the hash of the characters from `start` up to `end`
+    fn range(&self, start: usize, end: usize) -> u64 {
+        self.prefixes[end].wrapping_sub(self.prefixes[start].wrapping_mul(self.powers[end - start]))
+    }
+
+    /// Get the hash of the ID without the characters at the given ascending positions, which is the
+    /// same as the hash of the ID that is left
+    fn without(&self, deleted: &[usize]) -> u64 {
+        let len = self.prefixes.len() - 1;
+        let mut hash = 0u64;
+        let mut start = 0;
+        for &end in deleted.iter().chain(Some(&len)) {
+            hash = hash.wrapping_mul(self.powers[end - start]).wrapping_add(self.range(start, end));
+            start = end + 1;
+        }
+        hash
+    }
+}
+
+/// Get all pairs of IDs that share a bucket, given every bucket an ID is in as (key, ID index)
+/// entries. An ID that is in the same bucket multiple times is only counted once.
+fn bucket_pairs(mut entries: Vec<(u64, usize)>) -> HashSet<(usize, usize)> {
+    entries.sort_unstable();
+    entries.dedup();
+
+    let mut pairs = HashSet::new();
+    for bucket in entries.chunk_by(|x, y| x.0 == y.0) {
+        for (n, (_, a)) in bucket.iter().enumerate() {
+            for (_, b) in &bucket[n + 1..] {
+                pairs.insert((*a, *b));
             }
         }
     }
+    pairs
+}
 
-    panic!("Did not find any matching IDs")
+/// Find the first pair of IDs, by their indices, that have the same length and differ in exactly
+/// one position. Every ID is put in one bucket per position, keyed by the hash of the ID with that
+/// position masked out, so only IDs that agree everywhere but one position are compared.
+fn find_one_apart(box_ids: &[String]) -> Option<(usize, usize)> {
+    let ids: Vec<Vec<char>> = box_ids.iter().map(|id| id.chars().collect()).collect();
+
+    let mut entries = vec![];
+    for (i, id) in ids.iter().enumerate() {
+        let hashes = PrefixHashes::new(id);
+        for pos in 0..id.len() {
+            let key = hashes.without(&[pos]).wrapping_mul(HASH_BASE).wrapping_add(pos as u64);
+            entries.push((key, i));
+        }
+    }
+
+    // Keys can collide, and equal IDs share every bucket, so check the candidates
+    bucket_pairs(entries).into_iter()
+        .filter(|(a, b)| {
+            let (a, b) = (&ids[*a], &ids[*b]);
+            a.len() == b.len() && a.iter().zip(b).filter(|(ca, cb)| ca != cb).count() == 1
+        })
+        .min()
+}
+
+/// Find all pairs of IDs that are at most `k` edits (insertions, deletions or changed characters)
+/// apart, ordered by their indices. Every ID is put in a bucket for every string that is left
+/// after deleting at most `k` of its characters. Two IDs within `k` edits of each other can both
+/// be turned into the same string that way, so only IDs that share a bucket have to be compared.
+/// Buckets are keyed by the hash of that string instead of the string itself, which keeps every
+/// bucket entry at a fixed small size. This is meant for small `k`, since every ID of length n is
+/// put into up to n choose k buckets for every number of deletions.
+fn find_similar(box_ids: &[String], k: usize) -> Vec<SimilarPair> {
+    let ids: Vec<Vec<char>> = box_ids.iter().map(|id| id.chars().collect()).collect();
+
+    // The deletions to try only depend on the length of an ID
+    let mut deletions_by_len: HashMap<usize, Vec<Vec<usize>>> = HashMap::new();
+    let mut entries = vec![];
+    for (i, id) in ids.iter().enumerate() {
+        let hashes = PrefixHashes::new(id);
+        let deletions = deletions_by_len.entry(id.len()).or_insert_with(|| {
+            (0..=k.min(id.len()))
+                .flat_map(|count| combinations(id.len(), count))
+                .collect()
+        });
+        for deleted in deletions.iter() {
+            entries.push((hashes.without(deleted), i));
+        }
+    }
+
+    // The same pair can be in multiple buckets, and sharing a bucket doesn't guarantee that the
+    // pair is within k edits, so check every candidate
+    let mut result: Vec<_> = bucket_pairs(entries).into_iter()
+        .filter_map(|(a, b)| {
+            edit_distance(&ids[a], &ids[b], k).map(|distance| SimilarPair { a, b, distance })
+        })
+        .collect();
+    result.sort_by_key(|p| (p.a, p.b));
+    result
+}
+
+/// Get the Levenshtein distance between two strings, or None as soon as it's clear that it's more
+/// than `limit`
+fn edit_distance(a: &[char], b: &[char], limit: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > limit {
+        return None;
+    }
+
+    // The distances between the start of a and every start of b
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut next = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let change = row[j] + if ca == cb { 0 } else { 1 };
+            next[j + 1] = change.min(row[j + 1] + 1).min(next[j] + 1);
+        }
+        if next.iter().all(|d| *d > limit) {
+            return None;
+        }
+        row = next;
+    }
+
+    Some(row[b.len()]).filter(|d| *d <= limit)
+}
+
+/// Get all ways to pick `k` out of `n` positions, as ascending positions. If k > n, all positions
+/// are picked.
+fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    let k = k.min(n);
+    let mut result = vec![];
+    let mut current: Vec<usize> = (0..k).collect();
+
+    loop {
+        result.push(current.clone());
+
+        // Find the rightmost position that can still be moved to the right
+        let movable = (0..k).rev().find(|i| current[*i] < n - k + i);
+        match movable {
+            Some(i) => {
+                current[i] += 1;
+                for j in i + 1..k {
+                    current[j] = current[j - 1] + 1;
+                }
+            }
+            None => break result
+        }
+    }
 }
 
 /// Count the occurrences of every distinct character in a string
@@ -118,7 +298,66 @@ mod test {
                 String::from("axcye"),
                 String::from("wvxyz"),
             ]),
-            String::from("fgij")
-        )
+            Some(String::from("fgij"))
+        );
+
+        assert_eq!(solve_part2(vec![String::from("abc"), String::from("xyz")]), None);
+    }
+
+    #[test]
+    fn test_find_similar() {
+        let box_ids = vec![
+            String::from("abcde"),
+            String::from("abxde"),
+            String::from("xbxde"),
+            String::from("abcde"),
+            String::from("vwxyz"),
+            String::from("abcd"),
+        ];
+
+        assert_eq!(find_similar(&box_ids, 1), vec![
+            SimilarPair { a: 0, b: 1, distance: 1 },
+            SimilarPair { a: 0, b: 3, distance: 0 },
+            SimilarPair { a: 0, b: 5, distance: 1 },
+            SimilarPair { a: 1, b: 2, distance: 1 },
+            SimilarPair { a: 1, b: 3, distance: 1 },
+            SimilarPair { a: 3, b: 5, distance: 1 },
+        ]);
+        assert_eq!(find_similar(&box_ids, 2).len(), 9);
+
+        // An insertion and a deletion at different ends
+        let box_ids = vec![String::from("xabcd"), String::from("abcdy")];
+        assert_eq!(find_similar(&box_ids, 1), vec![]);
+        assert_eq!(find_similar(&box_ids, 2), vec![SimilarPair { a: 0, b: 1, distance: 2 }]);
+        assert_eq!(edit_distance(&['k', 'i', 't'], &['s', 'i', 't', 's'], 5), Some(2));
+        assert_eq!(combinations(4, 2).len(), 6);
+    }
+
+    #[test]
+    fn test_many_ids() {
+        // Random IDs like the puzzle's, with a few similar pairs hidden among them
+        let mut seed = 2018u64;
+        let mut box_ids: Vec<String> = (0..20_000)
+            .map(|_| {
+                (0..26)
+                    .map(|_| {
+                        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                        (b'a' + (seed >> 33) as u8 % 26) as char
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let changed = format!("{}#{}", &box_ids[14_000][..5], &box_ids[14_000][6..]);
+        let shortened = box_ids[8_000][1..].to_owned();
+        box_ids[18_000] = changed;
+        box_ids[19_000] = shortened;
+
+        assert_eq!(find_similar(&box_ids, 1), vec![
+            SimilarPair { a: 8_000, b: 19_000, distance: 1 },
+            SimilarPair { a: 14_000, b: 18_000, distance: 1 },
+        ]);
+        assert_eq!(find_one_apart(&box_ids), Some((14_000, 18_000)));
+        assert_eq!(solve_part2(box_ids).map(|common| common.len()), Some(25));
     }
 }
@@ -1,11 +1,15 @@
 //! Solutions for https://adventofcode.com/2018/day/20
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::iter::Peekable;
-use std::str::Chars;
+use std::mem;
+use std::slice;
 
 use day20::Direction::*;
 use utils::data::load_data;
+use utils::matrix::Matrix;
+use utils::search::bfs;
+use utils::search::bfs_to;
+use utils::search::Search;
 
 #[allow(dead_code)]
 pub fn part1() {
@@ -17,134 +21,232 @@ pub fn part2() {
     println!("{}", solve_part2(get_puzzle_input()));
 }
 
-type Trace = Vec<Direction>;
+/// Print the map of the facility, along with the route to one of the rooms that is furthest away
+#[allow(dead_code)]
+pub fn visualize_part1() {
+    let graph = build_graph(&get_puzzle_input());
+    println!("{}", render_map(&graph));
+
+    let furthest = solve_part1(get_puzzle_input());
+    let rooms = rooms_at_distance(&graph, furthest);
+    println!("{} rooms are {} doors away", rooms.len(), furthest);
+
+    let path = shortest_path(&graph, (0, 0), rooms[0]).unwrap();
+    let route: Vec<_> = path.iter().map(|(y, x)| format!("{},{}", x, y)).collect();
+    println!("Route to {:?}: {}", rooms[0], route.join(" -> "));
+}
+
+/// A room in the facility, as (y, x) relative to the starting room
+pub type Room = (i32, i32);
 
-type TracesChoice = Vec<Trace>;
+pub type Trace = Vec<Direction>;
 
-type Graph = HashMap<(i32, i32), HashSet<(i32, i32)>>;
+pub type TracesChoice = Vec<Trace>;
 
-enum Direction {
+/// The doors of every room, as the rooms they lead to
+pub type Graph = HashMap<Room, HashSet<Room>>;
+
+pub enum Direction {
     Step(char),
     SubTrace(Box<TracesChoice>),
 }
 
-fn solve_part1(trace: Trace) -> u32 {
-    get_furthest_node(build_graph(trace), 0, 0)
+impl Drop for Direction {
+    // Tear down nested groups iteratively, so that deeply nested traces can't overflow the stack
+    fn drop(&mut self) {
+        let mut pending = vec![];
+        if let SubTrace(choice) = self {
+            pending.push(mem::take(&mut **choice));
+        }
+        while let Some(choice) = pending.pop() {
+            for trace in choice {
+                for mut dir in trace {
+                    if let SubTrace(choice) = &mut dir {
+                        pending.push(mem::take(&mut **choice));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn solve_part1(trace: Trace) -> usize {
+    get_node_distances(&build_graph(&trace)).max_distance().unwrap()
 }
 
 fn solve_part2(trace: Trace) -> usize {
-    get_node_distances(build_graph(trace), 0, 0).values()
-        .filter(|d| **d >= 1000)
-        .count()
+    get_node_distances(&build_graph(&trace)).count_at_least(1000)
 }
 
-fn get_furthest_node(graph: Graph, y: i32, x: i32) -> u32 {
-    *get_node_distances(graph, y, x).values().max().unwrap()
+fn get_node_distances(graph: &Graph) -> Search<Room> {
+    bfs((0, 0), |node| neighbours(graph, node))
 }
 
-fn get_node_distances(graph: Graph, y: i32, x: i32) -> HashMap<(i32, i32), u32> {
-    let mut node_distances = HashMap::new();
-    node_distances.insert((y, x), 0);
+/// Get the rooms that a room has doors to, where rooms that aren't on the map have none
+fn neighbours(graph: &Graph, room: &Room) -> Vec<Room> {
+    graph.get(room).map(|doors| doors.iter().cloned().collect()).unwrap_or_default()
+}
 
-    let mut frontier = vec![(y, x)];
-    let mut distance = 0;
+/// Find a path through the least doors between two rooms, including both rooms
+pub fn shortest_path(graph: &Graph, from: Room, to: Room) -> Option<Vec<Room>> {
+    if !graph.contains_key(&from) {
+        return None;
+    }
+    bfs_to(from, |node| neighbours(graph, node), |node| *node == to).goal_path()
+}
 
-    loop {
-        distance += 1;
-        let mut new_frontier = vec![];
-
-        for (y, x) in frontier {
-            for (y2, x2) in graph[&(y, x)].iter() {
-                let loc = (*y2, *x2);
-                if node_distances.get(&loc).map_or(true, |d| distance < *d) {
-                    node_distances.insert(loc, distance);
-                    new_frontier.push(loc);
-                }
-            }
-        }
-        frontier = new_frontier;
-        if frontier.is_empty() {
-            break node_distances;
+/// Get all rooms that can be reached from the starting room by passing through exactly a number of
+/// doors, and no less, in reading order
+pub fn rooms_at_distance(graph: &Graph, distance: usize) -> Vec<Room> {
+    let mut rooms: Vec<_> = get_node_distances(graph).distances().iter()
+        .filter(|(_, d)| **d == distance)
+        .map(|(room, _)| *room)
+        .collect();
+    rooms.sort();
+    rooms
+}
+
+/// Render the map in the puzzle's notation, where every room and door takes up one character
+/// and the starting room is marked with an X
+pub fn render_map(graph: &Graph) -> String {
+    let min_y = graph.keys().map(|r| r.0).min().unwrap_or(0);
+    let max_y = graph.keys().map(|r| r.0).max().unwrap_or(0);
+    let min_x = graph.keys().map(|r| r.1).min().unwrap_or(0);
+    let max_x = graph.keys().map(|r| r.1).max().unwrap_or(0);
+
+    let height = (max_y - min_y + 1) as usize * 2 + 1;
+    let width = (max_x - min_x + 1) as usize * 2 + 1;
+    let mut map = Matrix::new(height, width, '#');
+
+    for (room, doors) in graph {
+        let y = (room.0 - min_y) as usize * 2 + 1;
+        let x = (room.1 - min_x) as usize * 2 + 1;
+        map[(y, x)] = if *room == (0, 0) { 'X' } else { '.' };
+
+        for door in doors {
+            let door_y = (y as i32 + door.0 - room.0) as usize;
+            let door_x = (x as i32 + door.1 - room.1) as usize;
+            map[(door_y, door_x)] = if door.0 == room.0 { '|' } else { '-' };
         }
     }
-}
 
-fn build_graph(trace: Trace) -> Graph {
-    add_trace_to_graph(trace, Graph::new(), 0, 0)
+    map.format_dense().unwrap()
 }
 
-fn add_trace_to_graph(trace: Trace, mut graph: Graph, mut y: i32, mut x: i32) -> Graph {
-    for dir in trace {
-        match dir {
-            Step(c) => {
-                let (y2, x2) = match c {
-                    'N' => (y - 1, x),
-                    'E' => (y, x + 1),
-                    'W' => (y, x - 1),
-                    'S' => (y + 1, x),
+/// Build the room graph by walking the trace from the starting room. Instead of following every
+/// combination of branches separately, the walker keeps track of the set of rooms it could be in,
+/// and merges the end rooms of all branches of a group before continuing after it. Nested groups
+/// are handled with an explicit stack, so the nesting depth is only limited by memory.
+pub fn build_graph(trace: &Trace) -> Graph {
+    // The starting room is on the map even if the trace doesn't leave it
+    let mut graph = Graph::new();
+    graph.insert((0, 0), HashSet::new());
+
+    let mut walks = vec![Walk { steps: trace.iter(), rooms: hashset! { (0, 0) } }];
+    let mut groups: Vec<Group> = vec![];
+
+    loop {
+        let walk = walks.last_mut().unwrap();
+        match walk.steps.next() {
+            Some(Step(c)) => {
+                let (dy, dx) = match c {
+                    'N' => (-1, 0),
+                    'E' => (0, 1),
+                    'W' => (0, -1),
+                    'S' => (1, 0),
                     _ => panic!("{}", c)
                 };
-                add_edge(&mut graph, y, x, y2, x2);
-                add_edge(&mut graph, y2, x2, y, x);
-                y = y2;
-                x = x2;
+                walk.rooms = walk.rooms.iter()
+                    .map(|&(y, x)| {
+                        let (y2, x2) = (y + dy, x + dx);
+                        add_edge(&mut graph, y, x, y2, x2);
+                        add_edge(&mut graph, y2, x2, y, x);
+                        (y2, x2)
+                    })
+                    .collect();
             }
-            SubTrace(c) => {
-                for trace in *c {
-                    graph = add_trace_to_graph(trace, graph, y, x);
+            Some(SubTrace(choice)) => {
+                let start = mem::take(&mut walk.rooms);
+                groups.push(Group { branches: choice.iter(), start, ends: HashSet::new() });
+                start_next_branch(&mut walks, &mut groups);
+            }
+            None => {
+                if groups.is_empty() {
+                    return graph;
                 }
+                let finished = walks.pop().unwrap();
+                groups.last_mut().unwrap().ends.extend(finished.rooms);
+                start_next_branch(&mut walks, &mut groups);
             }
         }
     }
-    graph
+}
+
+/// A trace that is being walked from a set of rooms at once
+struct Walk<'a> {
+    steps: slice::Iter<'a, Direction>,
+    rooms: HashSet<Room>,
+}
+
+/// A group of branches that is being walked one branch at a time
+struct Group<'a> {
+    branches: slice::Iter<'a, Trace>,
+    // The rooms where every branch starts
+    start: HashSet<Room>,
+    // The rooms where the finished branches ended
+    ends: HashSet<Room>,
+}
+
+/// Start walking the next branch of the innermost group, or continue after the group from all of
+/// its end rooms if every branch was walked.
+fn start_next_branch<'a>(walks: &mut Vec<Walk<'a>>, groups: &mut Vec<Group<'a>>) {
+    let group = groups.last_mut().unwrap();
+    match group.branches.next() {
+        Some(branch) => walks.push(Walk { steps: branch.iter(), rooms: group.start.clone() }),
+        None => {
+            let group = groups.pop().unwrap();
+            walks.last_mut().unwrap().rooms = group.ends;
+        }
+    }
 }
 
 fn add_edge(graph: &mut Graph, y1: i32, x1: i32, y2: i32, x2: i32) {
-    graph.entry((y1, x1)).or_insert(HashSet::new()).insert((y2, x2));
+    graph.entry((y1, x1)).or_default().insert((y2, x2));
 }
 
 fn get_puzzle_input() -> Trace {
     parse_input(load_data("day20"))
 }
 
-fn parse_input(input: String) -> Trace {
-    let mut chars = input.chars().peekable();
+/// Parse the regex into a trace, using a stack for the groups that are still open
+pub fn parse_input(input: String) -> Trace {
+    let mut chars = input.chars();
     assert_eq!(chars.next(), Some('^'));
-    let trace = parse_trace(&mut chars);
-    assert_eq!(chars.next(), Some('$'));
-    trace
-}
 
-fn parse_trace(chars: &mut Peekable<Chars>) -> Trace {
-    let mut result = Trace::new();
-    loop {
-        let next = if let Some(p) = chars.peek() { *p } else { break; };
-        result.push(match next {
-            'N' | 'E' | 'W' | 'S' => Step(chars.next().unwrap()),
-            '(' => SubTrace(Box::new(parse_traces_choice(chars))),
-            _ => break
-        });
-    }
-    result
-}
+    // For every open group, the trace it is part of along with its branches so far
+    let mut open: Vec<(Trace, TracesChoice)> = vec![];
+    let mut trace = Trace::new();
 
-fn parse_traces_choice(chars: &mut Peekable<Chars>) -> TracesChoice {
-    let mut result = TracesChoice::new();
-    assert_eq!(chars.next(), Some('('));
     loop {
-        let next = if let Some(p) = chars.peek() { *p } else { break; };
-        if !result.is_empty() {
-            if next == ')' {
-                break;
+        match chars.next() {
+            Some(c @ 'N') | Some(c @ 'E') | Some(c @ 'W') | Some(c @ 'S') => trace.push(Step(c)),
+            Some('(') => open.push((mem::take(&mut trace), TracesChoice::new())),
+            Some('|') => {
+                let branches = &mut open.last_mut().expect("| outside of a group").1;
+                branches.push(mem::take(&mut trace));
             }
-            if next == '|' {
-                chars.next();
+            Some(')') => {
+                let (outer, mut branches) = open.pop().expect("Unbalanced )");
+                branches.push(mem::replace(&mut trace, outer));
+                trace.push(SubTrace(Box::new(branches)));
             }
+            Some('$') => break,
+            c => panic!("Unexpected {:?}", c)
         }
-        result.push(parse_trace(chars));
     }
-    assert_eq!(chars.next(), Some(')'));
-    result
+
+    assert!(open.is_empty(), "Unbalanced (");
+    trace
 }
 
 #[cfg(test)]
@@ -184,4 +286,67 @@ mod test {
             31
         );
     }
+
+    #[test]
+    fn test_deep_nesting() {
+        let depth = 100_000;
+        let input = format!("^{}|E{}$", "(N".repeat(depth), ")".repeat(depth));
+        assert_eq!(solve_part1(parse_input(input)), depth);
+    }
+
+    #[test]
+    fn test_many_branches() {
+        // Following every combination of branches separately would take 2^500 walks
+        let input = format!("^{}E$", "(N|S)".repeat(500));
+        assert_eq!(solve_part1(parse_input(input)), 501);
+    }
+
+    #[test]
+    fn test_render_map() {
+        let graph = build_graph(&parse_input(String::from("^ENWWW(NEEE|SSE(EE|N))$")));
+
+        let expected = String::from(r"
+#########
+#.|.|.|.#
+#-#######
+#.|.|.|.#
+#-#####-#
+#.#.#X|.#
+#-#-#####
+#.|.|.|.#
+#########");
+
+        assert_eq!(render_map(&graph), expected.trim());
+    }
+
+    #[test]
+    fn test_queries() {
+        let graph = build_graph(&parse_input(String::from("^ENWWW(NEEE|SSE(EE|N))$")));
+
+        assert_eq!(
+            shortest_path(&graph, (0, 0), (1, 1)),
+            Some(vec![
+                (0, 0), (0, 1), (-1, 1), (-1, 0), (-1, -1), (-1, -2),
+                (0, -2), (1, -2), (1, -1), (1, 0), (1, 1)
+            ])
+        );
+        assert_eq!(shortest_path(&graph, (0, 0), (5, 5)), None);
+        assert_eq!(shortest_path(&graph, (1, -1), (1, -1)), Some(vec![(1, -1)]));
+
+        assert_eq!(rooms_at_distance(&graph, 10), vec![(1, 1)]);
+        assert_eq!(rooms_at_distance(&graph, 9), vec![(-2, 1), (0, -1), (1, 0)]);
+        assert_eq!(rooms_at_distance(&graph, 0), vec![(0, 0)]);
+        assert_eq!(rooms_at_distance(&graph, 11), vec![]);
+        assert_eq!(shortest_path(&graph, (7, 7), (0, 0)), None);
+    }
+
+    #[test]
+    fn test_no_steps() {
+        let graph = build_graph(&parse_input(String::from("^$")));
+        assert_eq!(solve_part1(parse_input(String::from("^$"))), 0);
+        assert_eq!(shortest_path(&graph, (0, 0), (0, 0)), Some(vec![(0, 0)]));
+        assert_eq!(shortest_path(&graph, (0, 0), (1, 0)), None);
+        assert_eq!(rooms_at_distance(&graph, 0), vec![(0, 0)]);
+        assert_eq!(render_map(&graph), "###\n#X#\n###");
+    }
 }
@@ -1,6 +1,5 @@
 //! Solutions for https://adventofcode.com/2018/day/21
-use std::collections::HashMap;
-
+use utils::cycle::CycleDetector;
 use utils::data::load_data;
 use utils::elfcode::parse_program;
 use utils::elfcode::Program;
@@ -26,28 +25,44 @@ fn solve_part1(program: Program) -> u64 {
 }
 
 fn solve_part2(program: Program) -> u64 {
-    // Let's assume that register d will stop changing at some point when the program goes into an
-    // infinite loop, or at least it will enter a repeating pattern. Find the last value before the
-    // first repeated value.
-    let mut counter = HashMap::new();
-    let mut result = 0;
+    // Every value of register d at the comparison only depends on the previous one, so eventually
+    // the values will repeat and the program goes into an infinite loop. Find the last value before
+    // the first repeated value, or the last value at all if the program halts before that.
+    let mut detector = CycleDetector::new();
+    let mut last_new = 0;
 
     {
         let mut vm = VM::load(program);
         vm.add_breakpoint(28, |s| {
             let d = s.register[3];
-            *counter.entry(d).or_insert(0) += 1;
-            if counter[&d] == 1 {
-                result = d;
+            let repeated = detector.push(d).is_some();
+            if !repeated {
+                last_new = d;
             }
-            counter[&d] > 1
+            repeated
         });
         vm.execute();
     }
-    
-    result
+
+    last_new
 }
 
 fn get_puzzle_input() -> Program {
     parse_program(load_data("day21"), 6)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_part2_halts() {
+        // Counts register 3 up at the comparison and halts once it's above 2, without repeating
+        let mut input = String::from("#ip 5\nseti 0 0 0\naddi 3 1 3\nseti 27 0 5\n");
+        input += &"seti 0 0 0\n".repeat(25);
+        input += "gtri 3 2 4\naddr 4 5 5\nseti 0 0 5\n";
+
+        assert_eq!(solve_part2(parse_program(input, 6)), 3);
+        assert_eq!(solve_part2(parse_program(String::from("seti 5 0 3"), 6)), 0);
+    }
+}
@@ -1,19 +1,74 @@
 //! Solutions for https://adventofcode.com/2018/day/22
-use std::collections::HashMap;
-use std::collections::HashSet;
-
 use utils::matrix::Matrix;
+use utils::search::astar;
+
+const CAVE_DEPTH: usize = 10914;
+const TARGET: (usize, usize) = (739, 9);
 
 #[allow(dead_code)]
 pub fn part1() {
-    println!("{}", solve_part1(10914, (739, 9)));
+    println!("{}", solve_part1(CAVE_DEPTH, TARGET));
 }
 
 #[allow(dead_code)]
 pub fn part2() {
-    println!("{}", solve_part2(10914, (739, 9)));
+    println!("{}", solve_part2(CAVE_DEPTH, TARGET));
+}
+
+#[allow(dead_code)]
+pub fn visualize_part2() {
+    let rescue = find_rescue(CAVE_DEPTH, TARGET);
+
+    println!("{}\n", render_cave(&rescue.risk_levels, TARGET, &rescue.route, rescue.extent(TARGET)));
+
+    for step in &rescue.route {
+        let (y, x, tool) = step.state;
+        println!("{:>5}: {:?} to {},{} with {}", step.minutes, step.action, x, y, TOOLS[tool]);
+    }
+
+    let (reached_y, reached_x) = rescue.extent(TARGET);
+    println!(
+        "Route reaches y={}, x={} within search bounds y={}, x={}",
+        reached_y, reached_x, rescue.bounds.0, rescue.bounds.1
+    );
 }
 
+/// Tool names by layer
+const TOOLS: [&str; 3] = ["neither", "torch", "climbing gear"];
+
+/// Location (y, x) and tool layer
+type State = (usize, usize, usize);
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+enum Action {
+    Start,
+    Move,
+    Switch,
+}
+
+/// A state along the route, the action that led to it and the minutes passed when it was reached
+#[derive(Debug, Eq, PartialEq)]
+struct Step {
+    state: State,
+    action: Action,
+    minutes: usize,
+}
+
+struct Rescue {
+    minutes: usize,
+    route: Vec<Step>,
+    risk_levels: Matrix<usize>,
+    /// The (y, x) bounds that the search was limited to
+    bounds: (usize, usize),
+}
+
+impl Rescue {
+    /// Get the largest (y, x) coordinates of the route and the target
+    fn extent(&self, target: (usize, usize)) -> (usize, usize) {
+        self.route.iter()
+            .fold(target, |(y, x), step| (y.max(step.state.0), x.max(step.state.1)))
+    }
+}
 
 fn solve_part1(cave_depth: usize, target: (usize, usize)) -> usize {
     let risk_levels = get_risk_levels(cave_depth, target, target);
@@ -28,6 +83,10 @@ fn solve_part1(cave_depth: usize, target: (usize, usize)) -> usize {
 }
 
 fn solve_part2(cave_depth: usize, target: (usize, usize)) -> usize {
+    find_rescue(cave_depth, target).minutes
+}
+
+fn find_rescue(cave_depth: usize, target: (usize, usize)) -> Rescue {
     // Pre-calculate the risk levels. Since we can go beyond the target x and y, let's set an
     // upper bound here.
     let manhattan = target.0 + target.1;
@@ -36,59 +95,91 @@ fn solve_part2(cave_depth: usize, target: (usize, usize)) -> usize {
 
     let risk_levels = get_risk_levels(cave_depth, target, (max_y, max_x));
 
-    // We'll use dijkstra's algorithm to find the fastest path to the target. Imagine the problem as
-    // a 3d grid where we have 3 layers where the layers represent the configurations of tools.
-    // Layer 0 is none, layer 1 is torch and layer 2 is climbing gear. Switching between layers
-    // takes 7 minutes, while switching between x,y coordinates takes 1 minute (but not every move
-    // is valid, depending on the layer). We have to end at the target, in layer 1. This gives us
-    // a graph with different length edges, so dijkstra's algorithm is perfect here.
-
-    let mut pending = HashSet::new();
-    let mut visited = HashSet::new();
+    // We'll use A* to find the fastest path to the target. Imagine the problem as a 3d grid where
+    // we have 3 layers where the layers represent the configurations of tools. Layer 0 is none,
+    // layer 1 is torch and layer 2 is climbing gear. Switching between layers takes 7 minutes,
+    // while switching between x,y coordinates takes 1 minute (but not every move is valid,
+    // depending on the layer). We have to end at the target, in layer 1. This gives us a graph with
+    // different length edges, and the manhattan distance plus a possible final switch to the torch
+    // is a lower bound for the remaining time.
     let start = (0, 0, 1); // torch at the entrance
     let target = (target.0, target.1, 1); // torch at target
-    let mut distances = HashMap::new();
-    distances.insert(start, 0);
-    pending.insert(start);
 
     let offsets = [(-1, 0, 0), (1, 0, 0), (0, -1, 0), (0, 1, 0), (0, 0, 1), (0, 0, 2)];
 
-    while !pending.is_empty() && !visited.contains(&target) {
-        let cur = pending.iter().min_by_key(|t| distances[t]).unwrap().clone();
-
-        let cur_dist = distances[&cur];
-        let move_dist = cur_dist + 1;
-        let switch_dist = cur_dist + 7;
-
-        // Calculate all distances through the current node to neighboring nodes.
+    let neighbours = |cur: &State| {
+        let mut result = vec![];
         for (oy, ox, ol) in offsets.iter() {
             let (y, x) = (cur.0 as i32 + oy, cur.1 as i32 + ox);
-            if x >= 0 && y >= 0 {
-                let dist = if *ol == 0 { move_dist } else { switch_dist };
+            if x >= 0 && y >= 0 && y as usize <= max_y && x as usize <= max_x {
+                let dist = if *ol == 0 { 1 } else { 7 };
 
                 let (y, x) = (y as usize, x as usize);
-                let layer = (cur.2 + ol) % 3;
-                let t = (y, x, layer);
+                let t = (y, x, (cur.2 + ol) % 3);
                 // Check if this is a valid location for the current equipment
-                let risk_level = risk_levels[(y, x)];
-                let is_valid = match (risk_level, t.2) {
-                    (0, 2) | (0, 1) | (1, 2) | (1, 0) | (2, 1) | (2, 0) => true,
-                    _ => false
-                };
-                if is_valid && !visited.contains(&t) {
-                    if !distances.contains_key(&t) || dist < distances[&t] {
-                        distances.insert(t, dist);
-                        pending.insert(t);
-                    }
+                if is_valid_tool(risk_levels[(y, x)], t.2) {
+                    result.push((t, dist));
                 }
             }
         }
+        result
+    };
+
+    let heuristic = |cur: &State| {
+        let manhattan = cur.0.abs_diff(target.0) + cur.1.abs_diff(target.1);
+        manhattan + if cur.2 == target.2 { 0 } else { 7 }
+    };
+
+    let path = astar(start, neighbours, heuristic, |cur| *cur == target)
+        .goal_path()
+        .unwrap();
 
-        visited.insert(cur);
-        pending.remove(&cur);
+    // Annotate every state with how we got there
+    let mut route = vec![Step { state: start, action: Action::Start, minutes: 0 }];
+    for state in path.into_iter().skip(1) {
+        let prev = route.last().unwrap();
+        let (action, duration) = if state.2 == prev.state.2 {
+            (Action::Move, 1)
+        } else {
+            (Action::Switch, 7)
+        };
+        let minutes = prev.minutes + duration;
+        route.push(Step { state, action, minutes });
+    }
+
+    Rescue {
+        minutes: route.last().unwrap().minutes,
+        route,
+        risk_levels,
+        bounds: (max_y, max_x),
+    }
+}
+
+fn is_valid_tool(risk_level: usize, tool: usize) -> bool {
+    matches!((risk_level, tool), (0, 2) | (0, 1) | (1, 2) | (1, 0) | (2, 1) | (2, 0))
+}
+
+/// Render the risk levels of the cave up to and including the extent in the puzzle's notation, with
+/// every location along the route marked with an X
+fn render_cave(
+    risk_levels: &Matrix<usize>,
+    target: (usize, usize),
+    route: &[Step],
+    extent: (usize, usize),
+) -> String {
+    let mut grid = Matrix::new(extent.0 + 1, extent.1 + 1, ' ');
+    for y in 0..=extent.0 {
+        for x in 0..=extent.1 {
+            grid[(y, x)] = ['.', '=', '|'][risk_levels[(y, x)]];
+        }
     }
+    for step in route {
+        grid[(step.state.0, step.state.1)] = 'X';
+    }
+    grid[(0, 0)] = 'M';
+    grid[target] = 'T';
 
-    distances[&target]
+    grid.format_dense().unwrap()
 }
 
 fn get_risk_levels(cave_depth: usize, target: (usize, usize), max: (usize, usize)) -> Matrix<usize> {
@@ -135,4 +226,42 @@ mod test {
             45
         );
     }
+
+    #[test]
+    fn test_route() {
+        let rescue = find_rescue(510, (10, 10));
+        let route = &rescue.route;
+
+        assert_eq!(route[0], Step { state: (0, 0, 1), action: Action::Start, minutes: 0 });
+        assert_eq!(route.last().unwrap().state, (10, 10, 1));
+
+        let moves = route.iter().filter(|s| s.action == Action::Move).count();
+        let switches = route.iter().filter(|s| s.action == Action::Switch).count();
+        assert_eq!(moves + 7 * switches, 45);
+    }
+
+    #[test]
+    fn test_render_cave() {
+        let risk_levels = get_risk_levels(510, (10, 10), (15, 15));
+        let expected = "\
+M=.|=.|.|=.
+.|=|=|||..|
+.==|....||=
+=.|....|.==
+=|..==...=.
+=||.=.=||=|
+|.=.===|||.
+|..==||=.|=
+.=..===..=|
+.======|||=
+.===|=|===T";
+
+        assert_eq!(render_cave(&risk_levels, (10, 10), &[], (10, 10)), expected);
+
+        let route = vec![
+            Step { state: (0, 0, 1), action: Action::Start, minutes: 0 },
+            Step { state: (1, 0, 1), action: Action::Move, minutes: 1 },
+        ];
+        assert!(render_cave(&risk_levels, (10, 10), &route, (10, 10)).starts_with("M=.|=.|.|=.\nX|"));
+    }
 }
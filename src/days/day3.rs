@@ -1,11 +1,8 @@
 //! Solutions for https://adventofcode.com/2018/day/3
-use std::collections::HashSet;
-
 use regex::Regex;
 
 use utils::data::load_data;
 use utils::data::non_empty_lines;
-use utils::matrix::Matrix;
 
 #[allow(dead_code)]
 pub fn part1() {
@@ -17,6 +14,29 @@ pub fn part2() {
     println!("{}", solve_combined(get_puzzle_input()).1);
 }
 
+/// Print every claim that conflicts with others, along with the overlapping areas
+#[allow(dead_code)]
+pub fn report() {
+    let claims = get_puzzle_input();
+
+    for overlap in overlap_report(&claims) {
+        if overlap.conflicts.is_empty() {
+            println!("#{} has no conflicts", overlap.id);
+            continue;
+        }
+        let conflicts: Vec<_> = overlap.conflicts.iter()
+            .map(|(id, area)| format!("#{} ({})", id, area))
+            .collect();
+        println!("#{} overlaps {} square inches with {}", overlap.id, overlap.area, conflicts.join(", "));
+    }
+
+    let rects: Vec<_> = claims.iter().map(|c| c.rect()).collect();
+    println!("Claimed multiple times: {}", area_covered(&rects, 2));
+}
+
+/// A rectangle as (left, top, right, bottom), where right and bottom are exclusive
+type Rect = (usize, usize, usize, usize);
+
 /// Claim represents an area of a bigger canvas defined by it's left/top offset and width/height
 #[derive(Debug, Eq, PartialEq)]
 struct Claim {
@@ -49,52 +69,187 @@ impl Claim {
             cap[5].parse().unwrap(),
         )
     }
+
+    fn rect(&self) -> Rect {
+        (self.left, self.top, self.left + self.width, self.top + self.height)
+    }
+
+    /// Get the area that is claimed by both this claim and another one, if any
+    fn intersect(&self, other: &Claim) -> Option<Rect> {
+        let (a, b) = (self.rect(), other.rect());
+        let rect = (a.0.max(b.0), a.1.max(b.1), a.2.min(b.2), a.3.min(b.3));
+        if rect.0 < rect.2 && rect.1 < rect.3 { Some(rect) } else { None }
+    }
+}
+
+/// The conflicts of a single claim
+#[derive(Debug, Eq, PartialEq)]
+pub struct ClaimOverlap {
+    pub id: u32,
+    /// The ids of the claims it overlaps with along with the size of each overlap, by id
+    pub conflicts: Vec<(u32, u64)>,
+    /// The total area of the claim that is also claimed by any other
+    pub area: u64,
+}
+
+/// Get the area that is claimed more than once, and the id of the claim that doesn't overlap with
+/// any other one
+fn solve_combined(claims: Vec<Claim>) -> (u64, u32) {
+    let rects: Vec<_> = claims.iter().map(|c| c.rect()).collect();
+
+    let valid_id = overlap_report(&claims).into_iter()
+        .find(|overlap| overlap.conflicts.is_empty())
+        .unwrap()
+        .id;
+
+    (area_covered(&rects, 2), valid_id)
 }
 
-fn solve_combined(claims: Vec<Claim>) -> (u32, u32) {
-    // Use a single signed integer to represent the state of the claims.
-    // 0 represents unclaimed cells
-    // Positive numbers represent the ID of a claim, if that claim is the only one
-    // -1 represents a cell that's claimed multiple times
-    let mut state = Matrix::new(1000, 1000, 0i32);
-
-    let mut conflicting_cells = 0;
-    let mut conflicting_ids = HashSet::new();
-
-    for claim in claims.iter() {
-        for x in claim.left..claim.left + claim.width {
-            for y in claim.top..claim.top + claim.height {
-                let idx = (y, x);
-                let cur = state[idx];
-                match cur {
-                    // Empty cell, assign it to the current claim
-                    0 => state[idx] = claim.id as i32,
-                    // Has one or multiple claims
-                    _ => {
-                        if cur > 0 {
-                            // Before the current claim, the cell was validly assigned to a single
-                            // different claim. Add that claim ID to the set of conflicting IDs and
-                            // mark the cell as conflicting (only the first time).
-                            conflicting_ids.insert(cur as u32);
-                            conflicting_cells += 1;
-                        }
-                        // In any case, the new claim is also conflicting. Add it to the set of 
-                        // conflicting IDs and mark the cell as conflicting.
-                        conflicting_ids.insert(claim.id);
-                        state[idx] = -1
-                    }
-                }
+/// Find the overlaps of every claim, in the order of the claims. Claims are visited from left to
+/// right, so each claim is only compared to the claims that it overlaps horizontally.
+fn overlap_report(claims: &[Claim]) -> Vec<ClaimOverlap> {
+    let mut order: Vec<usize> = (0..claims.len()).collect();
+    order.sort_by_key(|i| claims[*i].left);
+
+    let mut intersections: Vec<Vec<(u32, Rect)>> = claims.iter().map(|_| vec![]).collect();
+    let mut active: Vec<usize> = vec![];
+
+    for i in order {
+        let claim = &claims[i];
+        active.retain(|a| claims[*a].rect().2 > claim.left);
+
+        for a in &active {
+            if let Some(rect) = claim.intersect(&claims[*a]) {
+                intersections[i].push((claims[*a].id, rect));
+                intersections[*a].push((claim.id, rect));
             }
         }
+        active.push(i);
+    }
+
+    claims.iter().zip(intersections)
+        .map(|(claim, mut overlaps)| {
+            overlaps.sort_by_key(|(id, _)| *id);
+            let rects: Vec<_> = overlaps.iter().map(|(_, r)| *r).collect();
+            ClaimOverlap {
+                id: claim.id,
+                conflicts: overlaps.iter().map(|(id, r)| (*id, area(r))).collect(),
+                area: area_covered(&rects, 1),
+            }
+        })
+        .collect()
+}
+
+fn area(rect: &Rect) -> u64 {
+    ((rect.2 - rect.0) * (rect.3 - rect.1)) as u64
+}
+
+/// Compute the area that is covered by at least `min_count` rectangles, without a grid. A vertical
+/// line sweeps from left to right, keeping track of how much of it is covered in a segment tree
+/// over the distinct top and bottom coordinates.
+fn area_covered(rects: &[Rect], min_count: usize) -> u64 {
+    let mut ys: Vec<usize> = rects.iter().flat_map(|r| vec![r.1, r.3]).collect();
+    ys.sort();
+    ys.dedup();
+    if ys.len() < 2 {
+        return 0;
     }
 
-    // Find the only ID that did not conflict with anything
-    let valid_id = claims.iter()
-        .map(|c| c.id)
-        .find(|id| !conflicting_ids.contains(id))
-        .unwrap();
+    // Rectangles start covering at their left edge and stop at their right edge
+    let mut events: Vec<(usize, &Rect, i32)> = rects.iter()
+        .flat_map(|r| vec![(r.0, r, 1), (r.2, r, -1)])
+        .collect();
+    events.sort_by_key(|e| e.0);
+
+    let mut tree = CoverTree::new(&ys, min_count);
+    let mut prev_x = 0;
+    let mut total = 0;
 
-    (conflicting_cells, valid_id)
+    for (x, rect, delta) in events {
+        total += (tree.covered_length() * (x - prev_x)) as u64;
+        prev_x = x;
+
+        let from = ys.binary_search(&rect.1).unwrap();
+        let to = ys.binary_search(&rect.3).unwrap();
+        tree.add(from, to, delta);
+    }
+
+    total
+}
+
+/// A segment tree over the segments between consecutive coordinates, that knows how long the part
+/// covered by at least some number of intervals is. Every node counts the intervals that cover
+/// all of it without covering its parent, and the length covered at least 0 up to `min_count`
+/// times by the intervals in its subtree.
+struct CoverTree<'a> {
+    ys: &'a [usize],
+    min_count: usize,
+    counts: Vec<i32>,
+    // For every node, min_count + 1 lengths
+    covered: Vec<usize>,
+}
+
+impl<'a> CoverTree<'a> {
+    fn new(ys: &'a [usize], min_count: usize) -> CoverTree<'a> {
+        let nodes = 4 * ys.len();
+        let mut tree = CoverTree {
+            ys,
+            min_count,
+            counts: vec![0; nodes],
+            covered: vec![0; nodes * (min_count + 1)],
+        };
+        tree.build(1, 0, ys.len() - 1);
+        tree
+    }
+
+    fn build(&mut self, node: usize, lo: usize, hi: usize) {
+        if hi - lo > 1 {
+            let mid = (lo + hi) / 2;
+            self.build(2 * node, lo, mid);
+            self.build(2 * node + 1, mid, hi);
+        }
+        self.pull(node, lo, hi);
+    }
+
+    fn covered_length(&self) -> usize {
+        // The root is node 1
+        self.covered[(self.min_count + 1) + self.min_count]
+    }
+
+    /// Add an interval that covers the segments from..to, or remove it with a negative delta
+    fn add(&mut self, from: usize, to: usize, delta: i32) {
+        let segments = self.ys.len() - 1;
+        self.update(1, 0, segments, from, to, delta);
+    }
+
+    fn update(&mut self, node: usize, lo: usize, hi: usize, from: usize, to: usize, delta: i32) {
+        if to <= lo || hi <= from {
+            return;
+        }
+        if from <= lo && hi <= to {
+            self.counts[node] += delta;
+        } else {
+            let mid = (lo + hi) / 2;
+            self.update(2 * node, lo, mid, from, to, delta);
+            self.update(2 * node + 1, mid, hi, from, to, delta);
+        }
+        self.pull(node, lo, hi);
+    }
+
+    /// Recompute the covered lengths of a node from its count and its children
+    fn pull(&mut self, node: usize, lo: usize, hi: usize) {
+        let k = self.min_count + 1;
+        let count = self.counts[node] as usize;
+        for j in 0..k {
+            self.covered[node * k + j] = if j <= count {
+                self.ys[hi] - self.ys[lo]
+            } else if hi - lo == 1 {
+                0
+            } else {
+                self.covered[2 * node * k + j - count] + self.covered[(2 * node + 1) * k + j - count]
+            };
+        }
+    }
 }
 
 fn get_puzzle_input() -> Vec<Claim> {
@@ -120,6 +275,50 @@ mod test {
         )
     }
 
+    #[test]
+    fn test_outside_default_canvas() {
+        assert_eq!(
+            solve_combined(vec![
+                Claim::new(1, 1500, 3, 4, 4),
+                Claim::new(2, 1502, 1, 4, 4),
+                Claim::new(3, 5, 2000, 2, 2),
+            ]),
+            (4, 3)
+        );
+        // Far away claims don't need a canvas that reaches them
+        assert_eq!(
+            solve_combined(vec![
+                Claim::new(1, 1 << 40, 1 << 40, 4, 4),
+                Claim::new(2, (1 << 40) + 2, 1 << 40, 4, 4),
+                Claim::new(3, 5, 5, 2, 2),
+            ]),
+            (8, 3)
+        )
+    }
+
+    #[test]
+    fn test_overlap_report() {
+        let claims = vec![
+            Claim::new(1, 1, 3, 4, 4),
+            Claim::new(2, 3, 1, 4, 4),
+            Claim::new(3, 5, 5, 2, 2),
+            Claim::new(4, 2, 2, 2, 3),
+        ];
+
+        assert_eq!(overlap_report(&claims), vec![
+            ClaimOverlap { id: 1, conflicts: vec![(2, 4), (4, 4)], area: 6 },
+            ClaimOverlap { id: 2, conflicts: vec![(1, 4), (4, 3)], area: 5 },
+            ClaimOverlap { id: 3, conflicts: vec![], area: 0 },
+            ClaimOverlap { id: 4, conflicts: vec![(1, 4), (2, 3)], area: 5 },
+        ]);
+
+        let rects: Vec<_> = claims.iter().map(|c| c.rect()).collect();
+        assert_eq!(area_covered(&rects, 1), 33);
+        assert_eq!(area_covered(&rects, 2), 7);
+        assert_eq!(area_covered(&rects, 3), 2);
+        assert_eq!(area_covered(&rects, 4), 0);
+    }
+
     #[test]
     fn test_parse_claim() {
         assert_eq!(
@@ -1,17 +1,20 @@
 //! Solutions for https://adventofcode.com/2018/day/4
+use std::cmp::Reverse;
 use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Error;
+use std::fmt::Formatter;
 
-use chrono::DateTime;
 use chrono::Duration;
-use chrono::Local;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
 use chrono::Timelike;
-use chrono::TimeZone;
 use regex::Regex;
 
 use utils::data::load_data;
-use utils::data::non_empty_lines;
 
-type Date = DateTime<Local>;
+// Dates have no time zone, so results don't depend on the machine they are computed on
+type Date = NaiveDateTime;
 
 #[allow(dead_code)]
 pub fn part1() {
@@ -23,87 +26,196 @@ pub fn part2() {
     println!("{}", solve_part2(get_puzzle_input()));
 }
 
-#[derive(Debug, Eq, PartialEq)]
+/// Print the sleep chart of all nights, followed by a summary per guard
+#[allow(dead_code)]
+pub fn chart() {
+    let schedule = Schedule::from_events(get_puzzle_input());
+    println!("{}", schedule.render());
+
+    for guard_id in schedule.guards() {
+        let nights = schedule.nights_of(guard_id).count();
+        let total = schedule.total_minutes_asleep(guard_id);
+        match schedule.most_asleep_minute(guard_id) {
+            Some((minute, count)) => println!(
+                "#{}: {} minutes asleep over {} nights, most often at 00:{:02} ({} times)",
+                guard_id, total, nights, minute, count
+            ),
+            None => println!("#{}: never asleep over {} nights", guard_id, nights)
+        }
+    }
+
+    let (minute, guards) = (0..60)
+        .map(|m| (m, schedule.guards_asleep_at(m)))
+        .max_by_key(|(_, guards)| guards.values().sum::<u32>())
+        .unwrap();
+    println!("Most guards are asleep at 00:{:02}: {:?}", minute, guards);
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
 struct DateEvent {
+    // The line in the log, starting at 1
+    line: usize,
     date: Date,
     event: Event,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 enum Event {
     BeginsShift { guard_id: u32 },
     FallsAsleep,
     WakesUp,
 }
 
-fn solve_part1(events: Vec<DateEvent>) -> u32 {
-    let spg = get_sleeps_per_guard(events);
+/// A single night of guard duty, with the minutes of the midnight hour that the guard was asleep
+#[derive(Debug, Eq, PartialEq)]
+pub struct Night {
+    pub date: NaiveDate,
+    pub guard_id: u32,
+    pub asleep: [bool; 60],
+}
 
-    let guard_with_most_sleeps = spg.iter()
-        .max_by_key(|(_, s)| s.len())
-        .unwrap()
-        .0;
+impl Night {
+    pub fn minutes_asleep(&self) -> u32 {
+        self.asleep.iter().filter(|a| **a).count() as u32
+    }
 
-    let most_likely_minute = *count_sleep_minutes(&spg[guard_with_most_sleeps]).iter()
-        .max_by_key(|(_, c)| *c)
-        .unwrap()
-        .0;
+    /// The midnight hour as a line of 60 characters, with a . for awake and a # for asleep
+    pub fn timeline(&self) -> String {
+        self.asleep.iter().map(|a| if *a { '#' } else { '.' }).collect()
+    }
+}
 
-    guard_with_most_sleeps * most_likely_minute
+/// All nights of guard duty in chronological order
+pub struct Schedule {
+    pub nights: Vec<Night>,
 }
 
-fn solve_part2(events: Vec<DateEvent>) -> u32 {
-    let spg = get_sleeps_per_guard(events);
+impl Schedule {
+    fn from_events(events: Vec<DateEvent>) -> Schedule {
+        let events = sort_events(events);
+        let mut nights: Vec<Night> = vec![];
+        let mut sleeping_since = None;
+
+        for evt in events {
+            match evt.event {
+                Event::BeginsShift { guard_id } => {
+                    // Guards that start before midnight are on duty for the next day
+                    let mut date = evt.date.date();
+                    if evt.date.hour() != 0 {
+                        date = date.succ();
+                    }
+                    nights.push(Night { date, guard_id, asleep: [false; 60] });
+                }
+                Event::FallsAsleep => sleeping_since = Some(evt.date),
+                Event::WakesUp => {
+                    let night = nights.last_mut().expect("Validated logs start with a shift");
+                    let since = sleeping_since.take().expect("Validated logs fall asleep first");
+                    for minute in minutes_between(since, evt.date) {
+                        night.asleep[minute.minute() as usize] = true;
+                    }
+                }
+            }
+        }
 
-    let mut best = (0, 0);
-    let mut max = 0;
+        Schedule { nights }
+    }
 
-    for (guard, sleeps) in spg {
-        let sleep_minutes = count_sleep_minutes(&sleeps);
-        let (minute, count) = sleep_minutes.iter()
-            .max_by_key(|(_, c)| *c)
-            .unwrap();
+    /// Get the ids of all guards, in ascending order
+    pub fn guards(&self) -> Vec<u32> {
+        let mut guards: Vec<_> = self.nights.iter().map(|n| n.guard_id).collect();
+        guards.sort();
+        guards.dedup();
+        guards
+    }
+
+    pub fn nights_of(&self, guard_id: u32) -> impl Iterator<Item=&Night> {
+        self.nights.iter().filter(move |n| n.guard_id == guard_id)
+    }
 
-        if *count > max {
-            max = *count;
-            best = (guard, *minute);
+    pub fn total_minutes_asleep(&self, guard_id: u32) -> u32 {
+        self.nights_of(guard_id).map(|n| n.minutes_asleep()).sum()
+    }
+
+    /// Count the nights a guard was asleep, for every minute of the midnight hour
+    pub fn minute_counts(&self, guard_id: u32) -> [u32; 60] {
+        let mut counts = [0; 60];
+        for night in self.nights_of(guard_id) {
+            for (minute, asleep) in night.asleep.iter().enumerate() {
+                if *asleep {
+                    counts[minute] += 1;
+                }
+            }
         }
+        counts
     }
 
-    best.0 * best.1
-}
+    /// Get the minute that a guard was asleep on the most nights, along with that number of
+    /// nights. The earliest minute wins on ties. Returns None if the guard never slept.
+    pub fn most_asleep_minute(&self, guard_id: u32) -> Option<(u32, u32)> {
+        let counts = self.minute_counts(guard_id);
+        let (minute, count) = counts.iter().enumerate()
+            .max_by_key(|(m, c)| (**c, Reverse(*m)))
+            .unwrap();
+
+        if *count == 0 { None } else { Some((minute as u32, *count)) }
+    }
+
+    /// Count the nights that every guard was asleep at a minute of the midnight hour. Guards that
+    /// were never asleep at that minute are left out.
+    pub fn guards_asleep_at(&self, minute: u32) -> HashMap<u32, u32> {
+        let mut result = HashMap::new();
+        for night in &self.nights {
+            if night.asleep[minute as usize] {
+                *result.entry(night.guard_id).or_insert(0) += 1;
+            }
+        }
+        result
+    }
 
-/// Count the number of times a relative minute between 00:00 and 01:00 occurs in a set of dates
-fn count_sleep_minutes(dates: &Vec<Date>) -> HashMap<u32, u32> {
-    let mut count_per_minute = HashMap::new();
-    for date in dates.iter() {
-        *count_per_minute.entry(date.minute()).or_insert(0) += 1;
+    /// Render the schedule as a chart in the format of the puzzle description
+    pub fn render(&self) -> String {
+        let tens: String = (0..60).map(|m| (b'0' + m / 10) as char).collect();
+        let ones: String = (0..60).map(|m| (b'0' + m % 10) as char).collect();
+
+        let ids: Vec<_> = self.nights.iter().map(|n| format!("#{}", n.guard_id)).collect();
+        let width = ids.iter().map(|id| id.len()).max().unwrap_or(0).max(2);
+        let indent = " ".repeat(width + 9);
+
+        let mut lines = vec![
+            format!("Date   {:<w$}  Minute", "ID", w = width),
+            format!("{}{}", indent, tens),
+            format!("{}{}", indent, ones),
+        ];
+        for (night, id) in self.nights.iter().zip(ids) {
+            lines.push(format!(
+                "{}  {:<w$}  {}", night.date.format("%m-%d"), id, night.timeline(), w = width
+            ));
+        }
+        lines.join("\n")
     }
-    count_per_minute
 }
 
+fn solve_part1(events: Vec<DateEvent>) -> u32 {
+    let schedule = Schedule::from_events(events);
 
-/// Collect all minutes of sleep per guard ID
-fn get_sleeps_per_guard(events: Vec<DateEvent>) -> HashMap<u32, Vec<Date>> {
-    let events = sort_events(events);
+    let guard_with_most_sleep = schedule.guards().into_iter()
+        .max_by_key(|g| schedule.total_minutes_asleep(*g))
+        .unwrap();
 
-    let mut result = HashMap::new();
+    let (minute, _) = schedule.most_asleep_minute(guard_with_most_sleep).unwrap();
 
-    let mut cur_guard = None;
-    let mut sleeping_since = None;
+    guard_with_most_sleep * minute
+}
 
-    for evt in events {
-        match evt.event {
-            Event::BeginsShift { guard_id } => cur_guard = Some(guard_id),
-            Event::FallsAsleep => sleeping_since = Some(evt.date),
-            Event::WakesUp => result
-                .entry(cur_guard.unwrap())
-                .or_insert(vec![])
-                .extend(minutes_between(sleeping_since.unwrap(), evt.date))
-        }
-    }
+fn solve_part2(events: Vec<DateEvent>) -> u32 {
+    let schedule = Schedule::from_events(events);
 
-    result
+    let (guard, (minute, _)) = schedule.guards().into_iter()
+        .filter_map(|g| schedule.most_asleep_minute(g).map(|m| (g, m)))
+        .max_by_key(|(_, (_, count))| *count)
+        .unwrap();
+
+    guard * minute
 }
 
 /// Sort events by date
@@ -121,80 +233,270 @@ fn minutes_between(from: Date, to: Date) -> Vec<Date> {
 
     while cur < to {
         result.push(cur);
-        cur = cur + one_minute;
+        cur += one_minute;
     }
 
     result
 }
 
 fn get_puzzle_input() -> Vec<DateEvent> {
-    parse_puzzle_input(load_data("day4"))
+    let log = parse_log(load_data("day4"));
+    for anomaly in &log.anomalies {
+        eprintln!("Warning: {}", anomaly);
+    }
+    log.events
 }
 
-fn parse_puzzle_input(input: String) -> Vec<DateEvent> {
-    non_empty_lines(input)
-        .into_iter()
-        .map(parse_event)
-        .collect()
+/// The usable events of a log in chronological order, along with everything that was wrong with it
+struct Log {
+    events: Vec<DateEvent>,
+    anomalies: Vec<Anomaly>,
+}
+
+/// A problem with a line of the log. The line is left out, so that it can't affect any results.
+#[derive(Debug, Eq, PartialEq)]
+enum Anomaly {
+    Malformed { line: usize, text: String },
+    /// The same event at the same time as on an earlier line
+    Duplicate { line: usize, of: usize },
+    /// A guard fell asleep or woke up before any shift began
+    NoGuard { line: usize },
+    /// A guard fell asleep while already asleep since an earlier line
+    Overlap { line: usize, asleep_since: usize },
+    /// A guard fell asleep and never woke up before the next shift or the end of the log
+    NeverWokeUp { line: usize },
+    /// A guard woke up without having fallen asleep
+    NeverFellAsleep { line: usize },
+    /// A guard slept outside of the midnight hour, from one line to another
+    OutsideMidnightHour { line: usize, until: usize },
+}
+
+impl Display for Anomaly {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self {
+            Anomaly::Malformed { line, text } => write!(f, "Line {}: malformed record '{}'", line, text),
+            Anomaly::Duplicate { line, of } => write!(f, "Line {}: duplicate of line {}", line, of),
+            Anomaly::NoGuard { line } => write!(f, "Line {}: no guard is on duty", line),
+            Anomaly::Overlap { line, asleep_since } => write!(
+                f, "Line {}: falls asleep while asleep since line {}", line, asleep_since
+            ),
+            Anomaly::NeverWokeUp { line } => write!(f, "Line {}: falls asleep but never wakes up", line),
+            Anomaly::NeverFellAsleep { line } => write!(f, "Line {}: wakes up without being asleep", line),
+            Anomaly::OutsideMidnightHour { line, until } => write!(
+                f, "Line {}: sleeps outside the midnight hour until line {}", line, until
+            ),
+        }
+    }
+}
+
+/// Parse a log and validate it. The records are sorted by time, and every record that doesn't make
+/// sense is reported and left out.
+fn parse_log(input: String) -> Log {
+    let mut events = vec![];
+    let mut anomalies = vec![];
+
+    for (i, text) in input.split('\n').enumerate() {
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        match parse_event(i + 1, text) {
+            Some(event) => events.push(event),
+            None => anomalies.push(Anomaly::Malformed { line: i + 1, text: String::from(text) })
+        }
+    }
+
+    let events = validate(sort_events(events), &mut anomalies);
+    anomalies.sort_by_key(|a| a.line());
+
+    Log { events, anomalies }
+}
+
+impl Anomaly {
+    fn line(&self) -> usize {
+        match *self {
+            Anomaly::Malformed { line, .. } |
+            Anomaly::Duplicate { line, .. } |
+            Anomaly::NoGuard { line } |
+            Anomaly::Overlap { line, .. } |
+            Anomaly::NeverWokeUp { line } |
+            Anomaly::NeverFellAsleep { line } |
+            Anomaly::OutsideMidnightHour { line, .. } => line
+        }
+    }
+}
+
+/// Walk through the sorted events and only keep those that form valid shifts
+fn validate(events: Vec<DateEvent>, anomalies: &mut Vec<Anomaly>) -> Vec<DateEvent> {
+    let mut result = vec![];
+    let mut seen: HashMap<(Date, &Event), usize> = HashMap::new();
+
+    let mut on_duty = false;
+    // A falls asleep event is held back until the matching wakes up event is found
+    let mut asleep: Option<&DateEvent> = None;
+
+    for evt in &events {
+        if let Some(of) = seen.get(&(evt.date, &evt.event)) {
+            anomalies.push(Anomaly::Duplicate { line: evt.line, of: *of });
+            continue;
+        }
+        seen.insert((evt.date, &evt.event), evt.line);
+
+        match evt.event {
+            Event::BeginsShift { .. } => {
+                if let Some(since) = asleep.take() {
+                    anomalies.push(Anomaly::NeverWokeUp { line: since.line });
+                }
+                on_duty = true;
+                result.push(evt.clone());
+            }
+            _ if !on_duty => anomalies.push(Anomaly::NoGuard { line: evt.line }),
+            Event::FallsAsleep => match asleep {
+                Some(since) => anomalies.push(
+                    Anomaly::Overlap { line: evt.line, asleep_since: since.line }
+                ),
+                None => asleep = Some(evt)
+            },
+            Event::WakesUp => match asleep.take() {
+                Some(since) => {
+                    let midnight = since.date.date().and_hms(0, 0, 0);
+                    if since.date.hour() != 0 || evt.date > midnight + Duration::hours(1) {
+                        anomalies.push(
+                            Anomaly::OutsideMidnightHour { line: since.line, until: evt.line }
+                        );
+                    } else {
+                        result.push(since.clone());
+                        result.push(evt.clone());
+                    }
+                }
+                None => anomalies.push(Anomaly::NeverFellAsleep { line: evt.line })
+            }
+        }
+    }
+
+    if let Some(since) = asleep {
+        anomalies.push(Anomaly::NeverWokeUp { line: since.line });
+    }
+
+    result
 }
 
 /// Parse the puzzle input representation of an event into a DateEvent object
-fn parse_event(event: String) -> DateEvent {
+fn parse_event(line: usize, event: &str) -> Option<DateEvent> {
     let re = Regex::new(
-        r"^\[1518-0*(\d+)-0*(\d+) 0*(\d+):0*(\d+)] (falls|wakes|Guard) (?:#(\d+))?"
+        r"^\[1518-0*(\d+)-0*(\d+) 0*(\d+):0*(\d+)] (falls asleep|wakes up|Guard #(\d+) begins shift)$"
     ).unwrap();
 
-    let cap = re.captures(&event).expect(&event);
+    let cap = re.captures(event)?;
 
-    DateEvent {
-        date: local_date(
-            cap[1].parse().unwrap(),
-            cap[2].parse().unwrap(),
-            cap[3].parse().unwrap(),
-            cap[4].parse().unwrap(),
-        ),
-        event: match &cap[5] {
-            "Guard" => Event::BeginsShift { guard_id: cap[6].parse().unwrap() },
-            "falls" => Event::FallsAsleep,
-            "wakes" => Event::WakesUp,
-            _ => panic!("Usupported event")
-        },
-    }
-}
+    let date = NaiveDate::from_ymd_opt(1518, cap[1].parse().ok()?, cap[2].parse().ok()?)?
+        .and_hms_opt(cap[3].parse().ok()?, cap[4].parse().ok()?, 0)?;
+
+    let event = match &cap[5] {
+        "falls asleep" => Event::FallsAsleep,
+        "wakes up" => Event::WakesUp,
+        _ => Event::BeginsShift { guard_id: cap[6].parse().ok()? }
+    };
 
-fn local_date(month: u32, day: u32, hour: u32, minute: u32) -> Date {
-    Local.ymd(1518, month, day).and_hms(hour, minute, 0)
+    Some(DateEvent { line, date, event })
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    fn naive_date(month: u32, day: u32, hour: u32, minute: u32) -> Date {
+        NaiveDate::from_ymd(1518, month, day).and_hms(hour, minute, 0)
+    }
+
     #[test]
     fn test_parse_event() {
         assert_eq!(
-            parse_event(String::from("[1518-11-01 00:00] Guard #10 begins shift")),
-            DateEvent {
-                date: local_date(11, 1, 0, 0),
+            parse_event(1, "[1518-11-01 00:00] Guard #10 begins shift"),
+            Some(DateEvent {
+                line: 1,
+                date: naive_date(11, 1, 0, 0),
                 event: Event::BeginsShift { guard_id: 10 },
-            }
+            })
         );
 
         assert_eq!(
-            parse_event(String::from("[1518-11-04 00:36] falls asleep")),
-            DateEvent {
-                date: local_date(11, 4, 0, 36),
+            parse_event(2, "[1518-11-04 00:36] falls asleep"),
+            Some(DateEvent {
+                line: 2,
+                date: naive_date(11, 4, 0, 36),
                 event: Event::FallsAsleep,
-            }
+            })
         );
 
         assert_eq!(
-            parse_event(String::from("[1518-11-05 23:55] wakes up")),
-            DateEvent {
-                date: local_date(11, 5, 23, 55),
+            parse_event(3, "[1518-11-05 23:55] wakes up"),
+            Some(DateEvent {
+                line: 3,
+                date: naive_date(11, 5, 23, 55),
                 event: Event::WakesUp,
-            }
+            })
         );
+
+        assert_eq!(parse_event(4, "[1518-13-01 00:00] wakes up"), None);
+        assert_eq!(parse_event(5, "[1518-11-01 00:00] Guard begins shift"), None);
+    }
+
+    #[test]
+    fn test_schedule() {
+        let schedule = Schedule::from_events(get_test_input());
+
+        assert_eq!(schedule.guards(), vec![10, 99]);
+        assert_eq!(schedule.total_minutes_asleep(10), 50);
+        assert_eq!(schedule.most_asleep_minute(10), Some((24, 2)));
+        assert_eq!(schedule.most_asleep_minute(99), Some((45, 3)));
+        assert_eq!(schedule.guards_asleep_at(45), hashmap! { 10 => 1, 99 => 3 });
+        assert_eq!(schedule.guards_asleep_at(24), hashmap! { 10 => 2 });
+
+        let expected = "Date   ID   Minute
+            000000000011111111112222222222333333333344444444445555555555
+            012345678901234567890123456789012345678901234567890123456789
+11-01  #10  .....####################.....#########################.....
+11-02  #99  ........................................##########..........
+11-03  #10  ........................#####...............................
+11-04  #99  ....................................##########..............
+11-05  #99  .............................................##########.....";
+        assert_eq!(schedule.render(), expected);
+    }
+
+    #[test]
+    fn test_validation() {
+        let raw_input = "[1518-11-01 00:05] falls asleep
+[1518-11-01 00:30] falls asleep
+[1518-11-01 00:00] Guard #10 begins shift
+[1518-11-01 00:25] wakes up
+
+[1518-11-01 00:25] wakes up
+[1518-11-01 00:40] wakes up
+[1518-11-02 00:00] Guard #99 begins shift
+[1518-11-02 00:10] falls asleep
+[1518-11-02 01:10] wakes up
+[1518-11-02 00:08] the guard sneezes
+[1518-11-03 00:00] Guard #10 begins shift
+[1518-11-03 00:50] falls asleep
+[1518-11-01 00:10] falls asleep
+[1518-11-01 00:45] wakes up";
+
+        let log = parse_log(raw_input.to_owned());
+        assert_eq!(log.anomalies, vec![
+            Anomaly::Duplicate { line: 6, of: 4 },
+            Anomaly::OutsideMidnightHour { line: 9, until: 10 },
+            Anomaly::Malformed { line: 11, text: String::from("[1518-11-02 00:08] the guard sneezes") },
+            Anomaly::NeverWokeUp { line: 13 },
+            Anomaly::Overlap { line: 14, asleep_since: 1 },
+            Anomaly::NeverFellAsleep { line: 15 },
+        ]);
+        assert_eq!(log.anomalies[0].to_string(), "Line 6: duplicate of line 4");
+
+        let schedule = Schedule::from_events(log.events);
+        assert_eq!(schedule.total_minutes_asleep(10), 30);
+        assert_eq!(schedule.total_minutes_asleep(99), 0);
+        assert_eq!(schedule.nights.len(), 3);
     }
 
     #[test]
@@ -232,6 +534,8 @@ mod test {
 [1518-11-05 00:45] falls asleep
 [1518-11-05 00:55] wakes up";
 
-        parse_puzzle_input(raw_input.to_owned())
+        let log = parse_log(raw_input.to_owned());
+        assert!(log.anomalies.is_empty());
+        log.events
     }
 }
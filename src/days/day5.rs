@@ -1,78 +1,146 @@
 //! Solutions for https://adventofcode.com/2018/day/5
 use std::collections::HashSet;
+use std::io;
+use std::io::BufReader;
+use std::io::Read;
+use std::thread;
 use std::time::Instant;
 
 use utils::data::load_data;
+use utils::data::open_data;
 
 #[allow(dead_code)]
 pub fn part1() {
-    println!("{}", solve_part1(&get_puzzle_input()));
+    println!("{}", solve_part1(open_data("day5")));
 }
 
 #[allow(dead_code)]
 pub fn part2() {
-    println!("{}", solve_part2(&get_puzzle_input()));
+    println!("{}", solve_part2(open_data("day5")));
 }
 
 #[allow(dead_code)]
 pub fn time_both_parts() {
-    let input_str = load_data("day5");
-    let input = parse_puzzle_input(input_str);
+    let input = load_data("day5");
 
     let start = Instant::now();
 
-    solve_part1(&input);
-    solve_part2(&input);
+    solve_part1(input.as_bytes());
+    solve_part2(input.as_bytes());
 
     let elapsed = start.elapsed();
     println!("{:?}", elapsed);
 }
 
+/// Print the first reactions of the full polymer
+#[allow(dead_code)]
+pub fn trace_part1() {
+    let mut reducer = Reducer::new(None);
+    reducer.trace = Some(vec![]);
+    reducer.feed(open_data("day5")).unwrap();
+
+    let reactions = reducer.trace.unwrap();
+    println!("{} reactions, {} units remaining", reactions.len(), reducer.stack.len());
+    for (a, b) in reactions.iter().take(20) {
+        println!("Units {} and {} reacted", a, b);
+    }
+}
+
 /// Unit type and unit polarity are the char and bool
-#[derive(Clone)]
+#[derive(Copy, Clone)]
 struct Unit(char, bool);
 
-fn solve_part1(units: &Vec<Unit>) -> u32 {
-    reduce(units, None)
+/// The positions of two units in the original polymer that reacted with each other
+type Reaction = (usize, usize);
+
+/// Reduces a polymer one unit at a time, so the polymer itself never has to be in memory. Only the
+/// units that did not react (yet) are kept on a stack.
+struct Reducer {
+    // The units that remain, along with their positions in the original polymer
+    stack: Vec<(usize, Unit)>,
+    // The number of units that were fed so far
+    position: usize,
+    ignore: Option<char>,
+    trace: Option<Vec<Reaction>>,
 }
 
-fn solve_part2(units: &Vec<Unit>) -> u32 {
-    let unique_types: HashSet<char> = units.iter()
-        .map(|u| u.0)
-        .collect();
+impl Reducer {
+    fn new(ignore: Option<char>) -> Reducer {
+        Reducer { stack: vec![], position: 0, ignore, trace: None }
+    }
 
-    unique_types.into_iter()
-        .map(|t| reduce(units, Some(t)))
-        .min().unwrap() as u32
-}
+    fn push(&mut self, unit: Unit) {
+        let position = self.position;
+        self.position += 1;
+
+        if Some(unit.0) == self.ignore {
+            return;
+        }
 
-fn reduce(units: &Vec<Unit>, ignore: Option<char>) -> u32 {
-    let mut stack = Vec::with_capacity(units.len());
-    for unit in units {
-        if Some(unit.0) == ignore {
-            continue;
+        match self.stack.last() {
+            Some((top_position, top)) if does_react(&unit, top) => {
+                if let Some(trace) = &mut self.trace {
+                    trace.push((*top_position, position));
+                }
+                self.stack.pop();
+            }
+            _ => self.stack.push((position, unit))
         }
-        if stack.last().map_or(false, |top| does_react(unit, top)) {
-            stack.pop();
-        } else {
-            stack.push(unit.clone());
+    }
+
+    /// Feed all units from a source, skipping anything that isn't a unit such as whitespace
+    fn feed(&mut self, source: impl Read) -> io::Result<()> {
+        for byte in BufReader::new(source).bytes() {
+            let c = byte? as char;
+            if c.is_ascii_alphabetic() {
+                self.push(Unit(c.to_ascii_lowercase(), c.is_ascii_uppercase()));
+            }
         }
+        Ok(())
+    }
+
+    fn units(&self) -> impl Iterator<Item=Unit> + '_ {
+        self.stack.iter().map(|(_, unit)| *unit)
     }
-    stack.len() as u32
 }
 
-fn does_react(a: &Unit, b: &Unit) -> bool {
-    a.0 == b.0 && a.1 != b.1
+fn solve_part1(source: impl Read) -> u32 {
+    let mut reducer = Reducer::new(None);
+    reducer.feed(source).unwrap();
+    reducer.stack.len() as u32
 }
 
-fn get_puzzle_input() -> Vec<Unit> {
-    parse_puzzle_input(load_data("day5"))
+/// Removing a unit type and then reducing gives the same result as doing so after reducing first,
+/// so every type only has to be removed from the already reduced polymer. The types are tried in
+/// parallel.
+fn solve_part2(source: impl Read) -> u32 {
+    let mut reduced = Reducer::new(None);
+    reduced.feed(source).unwrap();
+
+    let mut unique_types: Vec<char> = reduced.units().map(|u| u.0).collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    unique_types.sort();
+
+    let reduced = &reduced;
+    thread::scope(|scope| {
+        let handles: Vec<_> = unique_types.into_iter()
+            .map(|t| scope.spawn(move || {
+                let mut reducer = Reducer::new(Some(t));
+                for unit in reduced.units() {
+                    reducer.push(unit);
+                }
+                reducer.stack.len()
+            }))
+            .collect();
+
+        // A polymer that reacts away completely leaves no types to remove
+        handles.into_iter().map(|h| h.join().unwrap()).min().unwrap_or(reduced.stack.len()) as u32
+    })
 }
 
-fn parse_puzzle_input(input: String) -> Vec<Unit> {
-    input.trim().chars().into_iter()
-        .map(|c| Unit(c.to_ascii_lowercase(), c.is_ascii_uppercase()))
-        .collect()
+fn does_react(a: &Unit, b: &Unit) -> bool {
+    a.0 == b.0 && a.1 != b.1
 }
 
 #[cfg(test)]
@@ -82,7 +150,7 @@ mod test {
     #[test]
     fn test_part1() {
         assert_eq!(
-            solve_part1(&parse_puzzle_input(String::from("  dabAcCaCBAcCcaDA  "))),
+            solve_part1("  dabAcCaCBAcCcaDA \n".as_bytes()),
             10
         )
     }
@@ -90,8 +158,25 @@ mod test {
     #[test]
     fn test_part2() {
         assert_eq!(
-            solve_part2(&parse_puzzle_input(String::from("dabAcCaCBAcCcaDA"))),
+            solve_part2("dabAcCaCBAcCcaDA".as_bytes()),
             4
-        )
+        );
+        assert_eq!(
+            solve_part2("aA".as_bytes()),
+            0
+        );
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut reducer = Reducer::new(None);
+        reducer.trace = Some(vec![]);
+        reducer.feed("dabAcCaCBAcCcaDA".as_bytes()).unwrap();
+
+        assert_eq!(reducer.trace, Some(vec![(4, 5), (3, 6), (10, 11)]));
+        let remaining: String = reducer.units()
+            .map(|u| if u.1 { u.0.to_ascii_uppercase() } else { u.0 })
+            .collect();
+        assert_eq!(remaining, "dabCBAcaDA");
     }
 }
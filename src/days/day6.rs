@@ -1,9 +1,13 @@
 //! Solutions for https://adventofcode.com/2018/day/6
+use std::fs::File;
+use std::io::Write;
+
 use utils::data::load_data;
 use utils::data::non_empty_lines;
-
-type Coord = (usize, usize);
-type Coords = Vec<Coord>;
+use utils::voronoi::Metric;
+use utils::voronoi::Point;
+use utils::voronoi::Region;
+use utils::voronoi::Voronoi;
 
 #[allow(dead_code)]
 pub fn part1() {
@@ -15,86 +19,61 @@ pub fn part2() {
     println!("{}", solve_part2(get_puzzle_input(), 10000));
 }
 
-fn solve_part1(coords: Coords) -> u32 {
-    let (min_x, max_x, min_y, max_y) = get_initial_bounds(&coords);
-
-    let mut areas = vec![0; coords.len()];
-    let mut infinite = vec![false; coords.len()];
-
-    for x in min_x..=max_x {
-        for y in min_y..=max_y {
-            let dists = get_coord_distances(x, y, &coords);
-            let closest_dist = *dists.iter().min().unwrap();
-            let closest_indices: Vec<_> = dists.iter().enumerate()
-                .filter(|(_, d)| **d == closest_dist)
-                .map(|(i, _)| i)
-                .collect();
-
-            if closest_indices.len() == 1 {
-                // Exactly one is closest
-                let closest_i = closest_indices[0];
-                areas[closest_i] += 1;
-                if x == min_x || x == max_x || y == min_y || y == max_y {
-                    // Touching the edge, so this one's infinite
-                    infinite[closest_i] = true;
-                }
-            }
-        }
+/// Compare the largest finite region under every metric, and write the Manhattan partition as text
+/// and as an image
+#[allow(dead_code)]
+pub fn export() {
+    let coords = get_puzzle_input();
+    for metric in [Metric::Manhattan, Metric::Chebyshev, Metric::Euclidean] {
+        let voronoi = Voronoi::new(coords.clone(), metric);
+        let infinite = voronoi.regions().into_iter().filter(|r| *r == Region::Infinite).count();
+        println!(
+            "{:?}: largest finite region {}, {} infinite regions",
+            metric, largest_finite_area(&voronoi), infinite
+        );
     }
 
-    areas.into_iter().zip(infinite.into_iter())
-        .filter(|(_, inf)| !*inf)
-        .map(|(a, _)| a)
-        .max().unwrap_or(0) as u32
-}
-
-fn solve_part2(coords: Coords, max: i32) -> u32 {
-    let (min_x, max_x, min_y, max_y) = get_initial_bounds(&coords);
-
-    let mut region = 0;
+    let voronoi = Voronoi::new(coords, Metric::Manhattan);
+    let (min, max) = voronoi.bounds();
 
-    for x in min_x..=max_x {
-        for y in min_y..=max_y {
-            let total_dist: i32 = get_coord_distances(x, y, &coords).iter().sum();
+    let path = "day6.txt";
+    let mut file = File::create(path).unwrap();
+    file.write_all(voronoi.render(min, max).as_bytes()).unwrap();
+    println!("Map written to {}", path);
 
-            if total_dist < max {
-                region += 1;
-            }
-        }
-    }
-
-    region
+    let path = "day6.ppm";
+    let mut file = File::create(path).unwrap();
+    file.write_all(&voronoi.render_ppm(min, max)).unwrap();
+    println!("Image written to {}", path);
 }
 
-fn get_coord_distances(x: i32, y: i32, coords: &Coords) -> Vec<i32> {
-    coords.iter()
-        .map(|(cx, cy)| (*cy as i32 - y).abs() + (*cx as i32 - x).abs())
-        .collect()
+fn solve_part1(coords: Vec<Point>) -> usize {
+    largest_finite_area(&Voronoi::new(coords, Metric::Manhattan))
 }
 
-fn get_initial_bounds(coords: &Coords) -> (i32, i32, i32, i32) {
-    let xs: Vec<_> = coords.iter().map(|c| c.0 as i32).collect();
-    let ys: Vec<_> = coords.iter().map(|c| c.1 as i32).collect();
-
-    let min_x = *xs.iter().min().unwrap();
-    let max_x = *xs.iter().max().unwrap();
-    let min_y = *ys.iter().min().unwrap();
-    let max_y = *ys.iter().max().unwrap();
+fn solve_part2(coords: Vec<Point>, max: i64) -> usize {
+    Voronoi::new(coords, Metric::Manhattan).total_distance_below(max as f64)
+}
 
-    (min_x, max_x, min_y, max_y)
+fn largest_finite_area(voronoi: &Voronoi) -> usize {
+    voronoi.regions().into_iter()
+        .filter_map(|r| match r {
+            Region::Finite(area) => Some(area),
+            Region::Infinite => None
+        })
+        .max().unwrap_or(0)
 }
 
-fn get_puzzle_input() -> Coords {
+fn get_puzzle_input() -> Vec<Point> {
     parse_puzzle_input(load_data("day6"))
 }
 
-fn parse_puzzle_input(input: String) -> Coords {
+fn parse_puzzle_input(input: String) -> Vec<Point> {
     non_empty_lines(input)
         .into_iter()
         .map(|l| l
             .split(", ")
-            .into_iter()
-            .map(|c| c.parse::<usize>().unwrap())
+            .map(|c| c.parse::<i64>().unwrap())
             .collect::<Vec<_>>()
         )
         .map(|c| (c[0], c[1]))
@@ -136,7 +115,7 @@ mod test {
         )
     }
 
-    fn get_test_input() -> Coords {
+    fn get_test_input() -> Vec<Point> {
         parse_puzzle_input(get_test_input_string())
     }
 
@@ -1,10 +1,17 @@
 //! Solutions for https://adventofcode.com/2018/day/7
-use std::collections::HashSet;
+use std::fmt::Display;
+use std::fmt::Error;
+use std::fmt::Formatter;
 
 use regex::Regex;
 
 use utils::data::load_data;
 use utils::data::non_empty_lines;
+use utils::scheduler::alphabetical;
+use utils::scheduler::longest_first;
+use utils::scheduler::Priority;
+use utils::scheduler::Scheduler;
+use utils::scheduler::shortest_first;
 
 #[allow(dead_code)]
 pub fn part1() {
@@ -16,117 +23,112 @@ pub fn part2() {
     println!("{}", solve_part2(get_puzzle_input(), 5, 60));
 }
 
+/// Print the part 2 schedule for every priority policy
+#[allow(dead_code)]
+pub fn gantt_part2() {
+    let scheduler = build_scheduler(get_puzzle_input(), 60);
+    let policies: Vec<(&str, Priority)> = vec![
+        ("Alphabetical", alphabetical),
+        ("Shortest first", shortest_first),
+        ("Longest first", longest_first),
+    ];
+
+    for (name, priority) in policies {
+        match scheduler.run(5, priority) {
+            Ok(schedule) => println!("{}:\n{}\n", name, schedule),
+            Err(e) => println!("{}", e)
+        }
+    }
+}
+
 #[derive(Eq, PartialEq, Debug)]
 struct Dependency {
-    id: char,
-    depends_on: char,
+    id: String,
+    depends_on: String,
 }
 
-fn solve_part1(dependencies: Vec<Dependency>) -> String {
-    let ids = get_sorted_ids(&dependencies);
-
-    let mut order = Vec::with_capacity(ids.len());
+#[derive(Eq, PartialEq, Debug)]
+enum ParseError {
+    /// The line doesn't describe a dependency
+    Malformed(String),
+    /// The step ID isn't a letter from A to Z, so it has no duration
+    NoDuration(String),
+}
 
-    for _ in 0..ids.len() {
-        let next = get_ready_ids(&ids, &dependencies, &order)[0];
-        order.push(next);
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self {
+            ParseError::Malformed(line) => write!(f, "Not a dependency: {}", line),
+            ParseError::NoDuration(id) => write!(
+                f, "Step {} has no duration, only steps A to Z do", id
+            ),
+        }
     }
+}
 
-    order.into_iter().collect()
+fn solve_part1(dependencies: Vec<Dependency>) -> String {
+    build_scheduler(dependencies, 0)
+        .order(alphabetical)
+        .unwrap_or_else(|e| panic!("{}", e))
+        .concat()
 }
 
 fn solve_part2(dependencies: Vec<Dependency>, num_workers: usize, base_duration: u32) -> u32 {
-    let mut pending_ids = get_sorted_ids(&dependencies);
-    let num_jobs = pending_ids.len();
-
-    // Track workers working on tasks and when that will be done
-    let mut jobs = vec![];
-
-    let mut resolved = Vec::with_capacity(num_jobs);
-    let mut time = 0;
-
-    loop {
-        // Handle finished jobs
-        let (finished, remaining) = jobs.iter().partition(|(_, t)| *t == time);
-        for (task, _) in finished {
-            resolved.push(task);
-        }
-        if resolved.len() == num_jobs {
-            break;
-        }
-        jobs = remaining;
-
-        // Get current availability of tasks and workers
-        let ready = get_ready_ids(&pending_ids, &dependencies, &resolved);
-        let available_workers = num_workers - jobs.len();
-
-        if ready.is_empty() || available_workers == 0 {
-            // No tasks or no workers, wait until a job finishes and try again
-            let wait_until = jobs.iter()
-                .map(|j| j.1)
-                .min().unwrap();
-
-            time = wait_until;
-            continue;
-        }
+    build_scheduler(dependencies, base_duration)
+        .run(num_workers, alphabetical)
+        .unwrap_or_else(|e| panic!("{}", e))
+        .makespan
+}
 
-        // Assign as many workers to tasks as possible
-        for task in ready.iter().take(available_workers) {
-            let task_duration = base_duration + 1 + (*task as u8 - 'A' as u8) as u32;
-            jobs.push((*task, time + task_duration));
-            pending_ids = pending_ids.into_iter().filter(|id| id != task).collect();
+fn build_scheduler(dependencies: Vec<Dependency>, base_duration: u32) -> Scheduler {
+    let mut scheduler = Scheduler::new();
+    for dependency in dependencies {
+        for id in &[&dependency.id, &dependency.depends_on] {
+            let duration = get_duration(id, base_duration)
+                .unwrap_or_else(|| panic!("{}", ParseError::NoDuration(id.to_string())));
+            scheduler.add_task(id, duration);
         }
+        scheduler.add_dependency(&dependency.id, &dependency.depends_on);
     }
-
-    time
-}
-
-fn get_sorted_ids(dependencies: &Vec<Dependency>) -> Vec<char> {
-    let mut ids: Vec<_> = dependencies.iter()
-        .flat_map(|d| vec![d.id, d.depends_on].into_iter())
-        .collect::<HashSet<_>>()
-        .into_iter()
-        .collect();
-
-    ids.sort();
-
-    ids
+    scheduler
 }
 
-fn get_ready_ids(
-    ids: &Vec<char>,
-    dependencies: &Vec<Dependency>,
-    resolved: &Vec<char>,
-) -> Vec<char> {
-    // Get all ids that are not yet resolved and that have no unresolved dependencies
-    ids.into_iter()
-        .filter(|id| !resolved.contains(id))
-        .filter(|id| {
-            dependencies.iter()
-                .filter(|d| d.id == **id)
-                .all(|d| resolved.contains(&d.depends_on))
-        })
-        .map(|c| *c)
-        .collect()
+/// Every step takes a base duration plus its position in the alphabet. Only steps that are a single
+/// letter from A to Z have a duration.
+fn get_duration(id: &str, base_duration: u32) -> Option<u32> {
+    let mut chars = id.chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter @ 'A'..='Z'), None) => Some(base_duration + 1 + (letter as u32 - 'A' as u32)),
+        _ => None
+    }
 }
 
 fn get_puzzle_input() -> Vec<Dependency> {
     non_empty_lines(load_data("day7"))
         .into_iter()
-        .map(parse_input_line)
+        .map(|line| parse_input_line(line).unwrap_or_else(|e| panic!("{}", e)))
         .collect()
 }
 
-fn parse_input_line(line: String) -> Dependency {
+fn parse_input_line(line: String) -> Result<Dependency, ParseError> {
     let re = Regex::new(
-        r"^Step ([A-Z]) must be finished before step ([A-Z]) can begin.$"
+        r"^Step (\S+) must be finished before step (\S+) can begin.$"
     ).unwrap();
 
-    let cap = re.captures(&line).unwrap();
-    Dependency {
-        id: cap[2].chars().next().unwrap(),
-        depends_on: cap[1].chars().next().unwrap(),
+    let cap = match re.captures(&line) {
+        Some(cap) => cap,
+        None => return Err(ParseError::Malformed(line.clone()))
+    };
+    for id in &[&cap[1], &cap[2]] {
+        if get_duration(id, 0).is_none() {
+            return Err(ParseError::NoDuration(id.to_string()));
+        }
     }
+
+    Ok(Dependency {
+        id: String::from(&cap[2]),
+        depends_on: String::from(&cap[1]),
+    })
 }
 
 #[cfg(test)]
@@ -137,10 +139,33 @@ mod test {
     fn test_parse() {
         assert_eq!(
             parse_input_line(String::from("Step B must be finished before step E can begin.")),
-            Dependency { id: 'E', depends_on: 'B' }
+            Ok(Dependency { id: String::from("E"), depends_on: String::from("B") })
         )
     }
 
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse_input_line(String::from("Step 1 must be finished before step E can begin.")),
+            Err(ParseError::NoDuration(String::from("1")))
+        );
+        assert_eq!(
+            parse_input_line(String::from("Step B must be finished before step Ab can begin.")),
+            Err(ParseError::NoDuration(String::from("Ab")))
+        );
+        assert_eq!(
+            parse_input_line(String::from("Step B must be finished before step É can begin."))
+                .unwrap_err()
+                .to_string(),
+            "Step É has no duration, only steps A to Z do"
+        );
+        assert_eq!(
+            parse_input_line(String::from("Step B before E")),
+            Err(ParseError::Malformed(String::from("Step B before E")))
+        );
+        assert_eq!(get_duration("Z", 60), Some(86));
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(
@@ -159,12 +184,12 @@ mod test {
 
     fn get_test_input() -> Vec<Dependency> {
         vec![
-            Dependency { id: 'A', depends_on: 'C' },
-            Dependency { id: 'F', depends_on: 'C' },
-            Dependency { id: 'B', depends_on: 'A' },
-            Dependency { id: 'D', depends_on: 'A' },
-            Dependency { id: 'E', depends_on: 'D' },
-            Dependency { id: 'E', depends_on: 'F' },
+            Dependency { id: String::from("A"), depends_on: String::from("C") },
+            Dependency { id: String::from("F"), depends_on: String::from("C") },
+            Dependency { id: String::from("B"), depends_on: String::from("A") },
+            Dependency { id: String::from("D"), depends_on: String::from("A") },
+            Dependency { id: String::from("E"), depends_on: String::from("D") },
+            Dependency { id: String::from("E"), depends_on: String::from("F") },
         ]
     }
 }
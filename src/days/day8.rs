@@ -1,6 +1,12 @@
 //! Solutions for https://adventofcode.com/2018/day/8
+use std::fmt::Display;
+use std::fmt::Error;
+use std::fmt::Formatter;
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::Write;
+
 use utils::data::load_data;
-use utils::misc::repeat;
 
 #[allow(dead_code)]
 pub fn part1() {
@@ -12,47 +18,233 @@ pub fn part2() {
     println!("{}", solve_part2(get_puzzle_input()));
 }
 
-struct Node {
-    children: Vec<Node>,
-    meta: Vec<usize>,
+/// Write the license tree as JSON and as a Graphviz graph, to inspect it
+#[allow(dead_code)]
+pub fn export() {
+    let tree = decode(&get_puzzle_input()).unwrap();
+
+    let path = "day8.json";
+    let mut file = File::create(path).unwrap();
+    file.write_all(tree.to_json().as_bytes()).unwrap();
+    println!("Tree written to {}", path);
+
+    let path = "day8.dot";
+    let mut file = File::create(path).unwrap();
+    file.write_all(tree.to_dot().as_bytes()).unwrap();
+    println!("Graph written to {}", path);
+}
+
+/// A node of the tree, with its children as indices into the tree's nodes
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Node {
+    pub children: Vec<usize>,
+    pub meta: Vec<usize>,
+}
+
+/// All nodes of a tree in the order in which they start in the data, so the root comes first and
+/// children always come after their parent
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Tree {
+    pub nodes: Vec<Node>,
+}
+
+/// Gets called for every node while walking a tree depth first
+pub trait Visitor {
+    /// Called before the children of a node are visited
+    fn enter(&mut self, _tree: &Tree, _node: usize, _depth: usize) {}
+
+    /// Called after all children of a node have been visited
+    fn leave(&mut self, _tree: &Tree, _node: usize, _depth: usize) {}
+}
+
+impl Tree {
+    /// Walk the tree depth first, visiting children in order
+    pub fn walk(&self, visitor: &mut impl Visitor) {
+        // The nodes being visited, with the number of their children visited so far
+        let mut stack = vec![(0, 0)];
+        visitor.enter(self, 0, 0);
+
+        while let Some((node, visited)) = stack.pop() {
+            let depth = stack.len();
+            match self.nodes[node].children.get(visited) {
+                Some(&child) => {
+                    stack.push((node, visited + 1));
+                    stack.push((child, 0));
+                    visitor.enter(self, child, depth + 1);
+                }
+                None => visitor.leave(self, node, depth)
+            }
+        }
+    }
+
+    /// Format the tree as nested JSON objects with the metadata and children of every node
+    pub fn to_json(&self) -> String {
+        let mut writer = JsonWriter { json: String::new(), first: vec![] };
+        self.walk(&mut writer);
+        writer.json
+    }
+
+    /// Format the tree as a Graphviz graph, with the metadata of every node as its label
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph tree {\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let label: Vec<_> = node.meta.iter().map(|m| m.to_string()).collect();
+            writeln!(dot, "    n{} [label=\"{}\"];", i, label.join(" ")).unwrap();
+            for child in &node.children {
+                writeln!(dot, "    n{} -> n{};", i, child).unwrap();
+            }
+        }
+        dot + "}\n"
+    }
+}
+
+struct JsonWriter {
+    json: String,
+    // Whether the next child of every open node is its first
+    first: Vec<bool>,
+}
+
+impl Visitor for JsonWriter {
+    fn enter(&mut self, tree: &Tree, node: usize, _depth: usize) {
+        if let Some(first) = self.first.last_mut() {
+            if !*first {
+                self.json.push(',');
+            }
+            *first = false;
+        }
+        let meta: Vec<_> = tree.nodes[node].meta.iter().map(|m| m.to_string()).collect();
+        write!(self.json, "{{\"meta\":[{}],\"children\":[", meta.join(",")).unwrap();
+        self.first.push(true);
+    }
+
+    fn leave(&mut self, _tree: &Tree, _node: usize, _depth: usize) {
+        self.json.push_str("]}");
+        self.first.pop();
+    }
+}
+
+/// Sums the metadata of all visited nodes
+struct MetaSum(usize);
+
+impl Visitor for MetaSum {
+    fn enter(&mut self, tree: &Tree, node: usize, _depth: usize) {
+        self.0 += tree.nodes[node].meta.iter().sum::<usize>();
+    }
+}
+
+/// Computes node values bottom up, keeping the values of the children of every open node
+struct NodeValue {
+    child_values: Vec<Vec<usize>>,
+}
+
+impl Visitor for NodeValue {
+    fn enter(&mut self, _tree: &Tree, _node: usize, _depth: usize) {
+        self.child_values.push(vec![]);
+    }
+
+    fn leave(&mut self, tree: &Tree, node: usize, _depth: usize) {
+        let children = self.child_values.pop().unwrap();
+        let meta = &tree.nodes[node].meta;
+        let value = if children.is_empty() {
+            meta.iter().sum()
+        } else {
+            meta.iter()
+                .filter(|i| **i > 0 && **i <= children.len())
+                .map(|i| children[i - 1])
+                .sum()
+        };
+
+        match self.child_values.last_mut() {
+            Some(parent) => parent.push(value),
+            // Keep the root value around as the result
+            None => self.child_values.push(vec![value])
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DecodeError {
+    /// The data ended within the node header or metadata block that starts at this offset and
+    /// needs this many values
+    Truncated { offset: usize, needed: usize },
+    /// The root node ended at this offset, but there was more data after it
+    Trailing { offset: usize, remaining: usize },
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self {
+            DecodeError::Truncated { offset, needed } => write!(
+                f, "Data ends within the {} values starting at offset {}", needed, offset
+            ),
+            DecodeError::Trailing { offset, remaining } => write!(
+                f, "Tree ends at offset {}, followed by {} more values", offset, remaining
+            ),
+        }
+    }
 }
 
 fn solve_part1(input: Vec<usize>) -> usize {
-    get_meta_sum(&decode(input))
+    get_meta_sum(&decode(&input).unwrap())
 }
 
 fn solve_part2(input: Vec<usize>) -> usize {
-    get_node_value(&decode(input))
+    get_node_value(&decode(&input).unwrap())
 }
 
-fn decode(input: Vec<usize>) -> Node {
-    /// Decode a node from the data stream recursively
-    fn decode_(read: &mut FnMut() -> usize) -> Node {
-        let header = repeat(read, 2);
-        Node {
-            children: repeat(&mut || decode_(read), header[0]),
-            meta: repeat(read, header[1]),
+/// Decode a tree from the data stream, keeping the nodes that still need children on a stack
+/// instead of recursing, so deeply nested trees don't overflow
+pub fn decode(input: &[usize]) -> Result<Tree, DecodeError> {
+    let mut offset = 0;
+    let mut read = |n: usize| {
+        if offset + n > input.len() {
+            return Err(DecodeError::Truncated { offset, needed: n });
         }
+        offset += n;
+        Ok(&input[offset - n..offset])
+    };
+
+    let mut nodes = vec![];
+    // Open nodes, with the number of children they still need and the size of their metadata
+    let mut stack: Vec<(usize, usize, usize)> = vec![];
+
+    loop {
+        let header = read(2)?;
+        nodes.push(Node { children: vec![], meta: vec![] });
+        let mut current = (nodes.len() - 1, header[0], header[1]);
+
+        // Finish every node that has all its children, until one needs another child
+        while current.1 == 0 {
+            let (node, _, meta_count) = current;
+            nodes[node].meta = read(meta_count)?.to_vec();
+
+            current = match stack.pop() {
+                Some(parent) => parent,
+                None => {
+                    if offset < input.len() {
+                        return Err(DecodeError::Trailing { offset, remaining: input.len() - offset });
+                    }
+                    return Ok(Tree { nodes });
+                }
+            };
+            nodes[current.0].children.push(node);
+            current.1 -= 1;
+        }
+
+        stack.push(current);
     }
-    // Start decoding from the start to build the root node
-    let mut iter = input.into_iter();
-    decode_(&mut || iter.next().unwrap())
 }
 
-fn get_meta_sum(node: &Node) -> usize {
-    node.meta.iter().map(|m| *m).sum::<usize>() +
-        node.children.iter().map(get_meta_sum).sum::<usize>()
+fn get_meta_sum(tree: &Tree) -> usize {
+    let mut sum = MetaSum(0);
+    tree.walk(&mut sum);
+    sum.0
 }
 
-fn get_node_value(node: &Node) -> usize {
-    if node.children.is_empty() {
-        get_meta_sum(node)
-    } else {
-        node.meta.iter()
-            .filter(|i| **i > 0 && **i <= node.children.len())
-            .map(|i| get_node_value(&node.children[(i - 1)]))
-            .sum::<usize>()
-    }
+fn get_node_value(tree: &Tree) -> usize {
+    let mut value = NodeValue { child_values: vec![] };
+    tree.walk(&mut value);
+    value.child_values[0][0]
 }
 
 fn get_puzzle_input() -> Vec<usize> {
@@ -60,7 +252,7 @@ fn get_puzzle_input() -> Vec<usize> {
 }
 
 fn parse_puzzle_input(input: String) -> Vec<usize> {
-    input.trim().split(' ').into_iter()
+    input.split_whitespace()
         .map(|c| c.parse::<usize>().unwrap())
         .collect()
 }
@@ -93,6 +285,54 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_decode_errors() {
+        let mut input = get_test_input();
+        input.push(5);
+        input.push(6);
+        assert_eq!(decode(&input), Err(DecodeError::Trailing { offset: 16, remaining: 2 }));
+
+        // The metadata of the root starts at 13
+        let error = decode(&input[..14]).unwrap_err();
+        assert_eq!(error, DecodeError::Truncated { offset: 13, needed: 3 });
+        assert_eq!(error.to_string(), "Data ends within the 3 values starting at offset 13");
+
+        // The header of the root's second child starts at 7
+        assert_eq!(decode(&input[..8]), Err(DecodeError::Truncated { offset: 7, needed: 2 }));
+        assert_eq!(decode(&[]), Err(DecodeError::Truncated { offset: 0, needed: 2 }));
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let depth = 100000;
+        let mut input = vec![1; depth * 3 + 3];
+        input[depth * 2] = 0;
+
+        let tree = decode(&input).unwrap();
+        assert_eq!(get_meta_sum(&tree), depth + 1);
+        assert_eq!(get_node_value(&tree), 1);
+    }
+
+    #[test]
+    fn test_export() {
+        let tree = decode(&[2, 1, 0, 1, 7, 0, 0, 2]).unwrap();
+        assert_eq!(
+            tree.to_json(),
+            r#"{"meta":[2],"children":[{"meta":[7],"children":[]},{"meta":[],"children":[]}]}"#
+        );
+        assert_eq!(
+            tree.to_dot(),
+            "digraph tree {
+    n0 [label=\"2\"];
+    n0 -> n1;
+    n0 -> n2;
+    n1 [label=\"7\"];
+    n2 [label=\"\"];
+}
+"
+        );
+    }
+
     fn get_test_input() -> Vec<usize> {
         vec![2, 3, 0, 3, 10, 11, 12, 1, 1, 0, 1, 99, 2, 1, 1, 2]
     }
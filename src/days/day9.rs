@@ -1,44 +1,325 @@
 //! Solutions for https://adventofcode.com/2018/day/9
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::fmt::Error;
+use std::fmt::Formatter;
+use std::time::Instant;
+
 use utils::circular_list::CircularList;
 
+const PLAYERS: usize = 473;
+const LAST_MARBLE: usize = 70904;
+
 #[allow(dead_code)]
 pub fn part1() {
-    println!("{}", solve(473, 70904));
+    println!("{}", play::<CircularList<usize>>(&Rules::new(PLAYERS, LAST_MARBLE).unwrap(), false).high_score());
 }
 
 #[allow(dead_code)]
 pub fn part2() {
-    println!("{}", solve(473, 70904 * 100));
+    println!("{}", play::<CircularList<usize>>(&Rules::new(PLAYERS, LAST_MARBLE * 100).unwrap(), false).high_score());
+}
+
+/// Compare the speed of both circle implementations on part 2
+#[allow(dead_code)]
+pub fn benchmark_part2() {
+    let rules = Rules::new(PLAYERS, LAST_MARBLE * 100).unwrap();
+
+    let start = Instant::now();
+    let score = play::<CircularList<usize>>(&rules, false).high_score();
+    println!("CircularList: {} in {:?}", score, start.elapsed());
+
+    let start = Instant::now();
+    let score = play::<IndexedList>(&rules, false).high_score();
+    println!("Indexed CircularList: {} in {:?}", score, start.elapsed());
+
+    let start = Instant::now();
+    let score = play::<VecDeque<usize>>(&rules, false).high_score();
+    println!("VecDeque: {} in {:?}", score, start.elapsed());
+}
+
+/// Show the circle and the scores of every player after a small game
+#[allow(dead_code)]
+pub fn show_small_game() {
+    let rules = Rules::custom(9, 25, 23, -7).unwrap();
+    let outcome = play::<VecDeque<usize>>(&rules, true);
+    println!("{} marbles: {:?}", outcome.marbles, outcome.circle.unwrap());
+    for (player, score) in outcome.scores.iter().enumerate() {
+        println!("Player {}: {}", player + 1, score);
+    }
+}
+
+/// The parameters of a marble game, which can only be created through the checked constructors
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Rules {
+    players: usize,
+    last_marble: usize,
+    /// Marbles that are a multiple of this are kept by the player instead of placed
+    scoring_multiple: usize,
+    /// Where the marble that is removed along with a scoring marble is, relative to the current one
+    removal_offset: isize,
+}
+
+impl Rules {
+    /// Get the rules of the puzzle for a number of players and a last marble
+    pub fn new(players: usize, last_marble: usize) -> Result<Rules, RulesError> {
+        Rules::custom(players, last_marble, 23, -7)
+    }
+
+    /// Get rules with a custom scoring multiple and removal offset. There has to be at least one
+    /// player, and the scoring multiple has to be at least 2 so marbles get placed at all.
+    pub fn custom(
+        players: usize,
+        last_marble: usize,
+        scoring_multiple: usize,
+        removal_offset: isize,
+    ) -> Result<Rules, RulesError> {
+        if players == 0 {
+            return Err(RulesError::NoPlayers);
+        }
+        if scoring_multiple < 2 {
+            return Err(RulesError::ScoringMultipleTooSmall(scoring_multiple));
+        }
+        Ok(Rules { players, last_marble, scoring_multiple, removal_offset })
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RulesError {
+    NoPlayers,
+    ScoringMultipleTooSmall(usize),
+}
+
+impl Display for RulesError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self {
+            RulesError::NoPlayers => write!(f, "The game needs at least one player"),
+            RulesError::ScoringMultipleTooSmall(multiple) => write!(
+                f, "The scoring multiple must be at least 2, got {}", multiple
+            ),
+        }
+    }
+}
+
+pub struct Outcome {
+    /// The score of every player, in order of turns
+    pub scores: Vec<usize>,
+    /// The number of marbles left in the circle
+    pub marbles: usize,
+    /// The marbles in the circle at the end, starting with the current one, if asked for
+    pub circle: Option<Vec<usize>>,
+}
+
+impl Outcome {
+    pub fn high_score(&self) -> usize {
+        self.scores.iter().cloned().max().unwrap_or(0)
+    }
+}
+
+/// A circle of marbles with a current marble, as needed to play the game
+pub trait Circle {
+    fn with_capacity(capacity: usize) -> Self;
+
+    /// Insert a marble after the current one and make it the current one
+    fn insert(&mut self, marble: usize);
+
+    /// Remove the current marble and make the next one the current one, or get None if the circle
+    /// is empty
+    fn remove(&mut self) -> Option<usize>;
+
+    /// Move the current marble clockwise (positive) or counter-clockwise (negative)
+    fn seek(&mut self, offset: isize);
+
+    fn len(&self) -> usize;
+
+    /// Get all marbles, starting with the current one and going clockwise
+    fn snapshot(&self) -> Vec<usize>;
+}
+
+impl Circle for CircularList<usize> {
+    fn with_capacity(capacity: usize) -> Self {
+        CircularList::with_capacity(capacity)
+    }
+
+    fn insert(&mut self, marble: usize) {
+        CircularList::insert(self, marble);
+    }
+
+    fn remove(&mut self) -> Option<usize> {
+        CircularList::remove(self)
+    }
+
+    fn seek(&mut self, offset: isize) {
+        CircularList::seek(self, offset);
+    }
+
+    fn len(&self) -> usize {
+        CircularList::len(self)
+    }
+
+    fn snapshot(&self) -> Vec<usize> {
+        self.iter().cloned().collect()
+    }
 }
 
-fn solve(players: usize, max_marble: usize) -> usize {
-    let mut scores = vec![0usize; players];
+/// A CircularList with its index enabled, which seeks in O(log n) steps at the cost of keeping
+/// the index up to date
+pub struct IndexedList(CircularList<usize>);
 
-    let mut circle = CircularList::with_capacity(max_marble + 1);
-    circle.insert(0usize);
+impl Circle for IndexedList {
+    fn with_capacity(capacity: usize) -> Self {
+        let mut list = CircularList::with_capacity(capacity);
+        list.enable_index();
+        IndexedList(list)
+    }
 
-    for marble in 1..=max_marble {
-        if marble % 23 == 0 {
-            scores[marble % players] += circle.seek(-7).remove().unwrap() + marble;
+    fn insert(&mut self, marble: usize) {
+        self.0.insert(marble);
+    }
+
+    fn remove(&mut self) -> Option<usize> {
+        self.0.remove()
+    }
+
+    fn seek(&mut self, offset: isize) {
+        self.0.seek(offset);
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn snapshot(&self) -> Vec<usize> {
+        self.0.snapshot()
+    }
+}
+
+/// The current marble is kept at the back, so that inserting after it is a push
+impl Circle for VecDeque<usize> {
+    fn with_capacity(capacity: usize) -> Self {
+        VecDeque::with_capacity(capacity)
+    }
+
+    fn insert(&mut self, marble: usize) {
+        self.push_back(marble);
+    }
+
+    fn remove(&mut self) -> Option<usize> {
+        let marble = self.pop_back();
+        if !self.is_empty() {
+            self.rotate_left(1);
+        }
+        marble
+    }
+
+    fn seek(&mut self, offset: isize) {
+        if self.is_empty() {
+            return;
+        }
+        let steps = offset.unsigned_abs() % self.len();
+        if offset > 0 {
+            self.rotate_left(steps);
         } else {
-            circle.next().insert(marble);
+            self.rotate_right(steps);
         }
     }
 
-    scores.into_iter().max().unwrap()
+    fn len(&self) -> usize {
+        VecDeque::len(self)
+    }
+
+    fn snapshot(&self) -> Vec<usize> {
+        let mut marbles: Vec<_> = self.iter().cloned().collect();
+        marbles.rotate_right(1);
+        marbles
+    }
+}
+
+/// Play a game with the given rules on a circle implementation, optionally keeping the final
+/// circle
+pub fn play<C: Circle>(rules: &Rules, snapshot: bool) -> Outcome {
+    let mut scores = vec![0usize; rules.players];
+
+    let mut circle = C::with_capacity(rules.last_marble + 1);
+    circle.insert(0);
+
+    for marble in 1..=rules.last_marble {
+        if marble % rules.scoring_multiple == 0 {
+            circle.seek(rules.removal_offset);
+            // With a scoring multiple of at least 2 the circle never runs empty, but score only the
+            // kept marble if it would
+            scores[(marble - 1) % rules.players] += circle.remove().unwrap_or(0) + marble;
+        } else {
+            circle.seek(1);
+            circle.insert(marble);
+        }
+    }
+
+    Outcome {
+        scores,
+        marbles: circle.len(),
+        circle: if snapshot { Some(circle.snapshot()) } else { None },
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    fn solve<C: Circle>(players: usize, last_marble: usize) -> usize {
+        play::<C>(&Rules::new(players, last_marble).unwrap(), false).high_score()
+    }
+
     #[test]
     fn test_solve() {
-        assert_eq!(solve(9, 25), 32);
-        assert_eq!(solve(10, 1618), 8317);
-        assert_eq!(solve(13, 7999), 146373);
-        assert_eq!(solve(17, 1104), 2764);
-        assert_eq!(solve(21, 6111), 54718);
-        assert_eq!(solve(30, 5807), 37305);
+        for solve in [solve::<CircularList<usize>>, solve::<IndexedList>, solve::<VecDeque<usize>>] {
+            assert_eq!(solve(9, 25), 32);
+            assert_eq!(solve(10, 1618), 8317);
+            assert_eq!(solve(13, 7999), 146373);
+            assert_eq!(solve(17, 1104), 2764);
+            assert_eq!(solve(21, 6111), 54718);
+            assert_eq!(solve(30, 5807), 37305);
+        }
+    }
+
+    #[test]
+    fn test_outcome() {
+        let rules = Rules::new(9, 25).unwrap();
+        let expected = vec![
+            25, 10, 21, 5, 22, 11, 1, 12, 6, 13, 3, 14, 7, 15, 0, 16, 8, 17, 4, 18, 19, 2, 24, 20
+        ];
+
+        for outcome in [play::<CircularList<usize>>(&rules, true), play::<VecDeque<usize>>(&rules, true)] {
+            assert_eq!(outcome.scores, vec![0, 0, 0, 0, 32, 0, 0, 0, 0]);
+            assert_eq!(outcome.marbles, 24);
+            assert_eq!(outcome.circle, Some(expected.clone()));
+        }
+
+        // Different rules, where every 5th marble scores and removes the marble 2 further
+        let rules = Rules::custom(2, 10, 5, 2).unwrap();
+        let outcome = play::<VecDeque<usize>>(&rules, true);
+        assert_eq!(outcome.scores, play::<IndexedList>(&rules, true).scores);
+        assert_eq!(outcome.circle, play::<IndexedList>(&rules, true).circle);
+
+        // The smallest scoring multiple keeps removing the only other marble
+        let rules = Rules::custom(1, 9, 2, -1).unwrap();
+        let outcome = play::<VecDeque<usize>>(&rules, true);
+        assert_eq!(outcome.circle, play::<CircularList<usize>>(&rules, true).circle);
+        assert_eq!(outcome.marbles, 2);
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        assert_eq!(Rules::new(0, 25), Err(RulesError::NoPlayers));
+        assert_eq!(Rules::custom(9, 25, 0, -7), Err(RulesError::ScoringMultipleTooSmall(0)));
+        assert_eq!(
+            Rules::custom(9, 25, 1, -7).unwrap_err().to_string(),
+            "The scoring multiple must be at least 2, got 1"
+        );
+
+        let mut circle = VecDeque::new();
+        assert_eq!(Circle::remove(&mut circle), None);
+        let mut circle = CircularList::new();
+        assert_eq!(Circle::remove(&mut circle), None);
     }
 }
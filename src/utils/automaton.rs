@@ -0,0 +1,364 @@
+//! Generic cellular automaton engine over 1d and 2d grids. The grid is a Matrix, where a 1d
+//! automaton is simply a matrix with a single row. Rules, neighbourhoods and boundary conditions
+//! are pluggable.
+
+use std::hash::Hash;
+use std::mem;
+
+use utils::cycle::CycleDetector;
+use utils::cycle::Evidence;
+use utils::cycle::extrapolate_linear;
+use utils::cycle::Prediction;
+use utils::matrix::Matrix;
+
+/// The (dy, dx) offsets of the cells that are passed to a rule, in that order
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Neighbourhood {
+    offsets: Vec<(i32, i32)>,
+}
+
+impl Neighbourhood {
+    pub fn new(offsets: Vec<(i32, i32)>) -> Neighbourhood {
+        Neighbourhood { offsets }
+    }
+
+    /// All cells within a Chebyshev distance of `radius`, excluding the cell itself
+    pub fn moore(radius: i32) -> Neighbourhood {
+        Neighbourhood::from_filter(radius, |dy, dx| dy != 0 || dx != 0)
+    }
+
+    /// All cells within a Manhattan distance of `radius`, excluding the cell itself
+    pub fn von_neumann(radius: i32) -> Neighbourhood {
+        Neighbourhood::from_filter(radius, |dy, dx| {
+            (dy != 0 || dx != 0) && dy.abs() + dx.abs() <= radius
+        })
+    }
+
+    /// A horizontal window of 2 * radius + 1 cells, centered on and including the cell itself.
+    /// This is the usual neighbourhood for 1d automata.
+    pub fn window(radius: i32) -> Neighbourhood {
+        Neighbourhood::new((-radius..=radius).map(|dx| (0, dx)).collect())
+    }
+
+    fn from_filter(radius: i32, filter: impl Fn(i32, i32) -> bool) -> Neighbourhood {
+        let mut offsets = vec![];
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                if filter(dy, dx) {
+                    offsets.push((dy, dx));
+                }
+            }
+        }
+        Neighbourhood::new(offsets)
+    }
+
+    pub fn offsets(&self) -> &[(i32, i32)] {
+        &self.offsets
+    }
+
+    /// The furthest vertical and horizontal distance that a cell can look at
+    fn reach(&self) -> (usize, usize) {
+        let ry = self.offsets.iter().map(|o| o.0.abs()).max().unwrap_or(0);
+        let rx = self.offsets.iter().map(|o| o.1.abs()).max().unwrap_or(0);
+        (ry as usize, rx as usize)
+    }
+}
+
+/// Determines what a rule sees when a neighbourhood extends beyond the edge of the grid
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Boundary<T> {
+    /// Every cell outside the grid has a fixed value
+    Constant(T),
+    /// The grid wraps around at its edges
+    Wrap,
+    /// The grid is infinite and filled with a background value. It is grown before every step so
+    /// that nothing can spill over the edge. The rule must keep a background cell that is only
+    /// surrounded by background unchanged.
+    Expand(T),
+}
+
+/// A rule computes the next value of a cell from its current value and the values of its
+/// neighbourhood.
+pub type Rule<T> = Box<dyn Fn(T, &[T]) -> T>;
+
+/// How `Automaton::advance` may shortcut the simulation
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Shortcut {
+    /// Stop as soon as a previously seen state repeats
+    Cycle,
+    /// Stop as soon as the metric has grown by the same amount for a number of generations in a
+    /// row, and assume it keeps doing so. This is a heuristic.
+    LinearGrowth { repeats: usize },
+}
+
+pub struct Automaton<T> {
+    current: Matrix<T>,
+    next: Matrix<T>,
+    neighbourhood: Neighbourhood,
+    boundary: Boundary<T>,
+    rule: Rule<T>,
+    // The world coordinates (y, x) of cell (0, 0) in the grid, which change as the grid expands
+    origin: (i64, i64),
+    pub generation: usize,
+}
+
+impl<T> Automaton<T> where T: Copy + PartialEq {
+    pub fn new(
+        grid: Matrix<T>,
+        neighbourhood: Neighbourhood,
+        boundary: Boundary<T>,
+        rule: impl Fn(T, &[T]) -> T + 'static,
+    ) -> Automaton<T> {
+        let next = grid.clone();
+        Automaton {
+            current: grid,
+            next,
+            neighbourhood,
+            boundary,
+            rule: Box::new(rule),
+            origin: (0, 0),
+            generation: 0,
+        }
+    }
+
+    /// Create a 1d automaton from a row of cells, which may be empty
+    pub fn from_row(
+        cells: &[T],
+        neighbourhood: Neighbourhood,
+        boundary: Boundary<T>,
+        rule: impl Fn(T, &[T]) -> T + 'static,
+    ) -> Automaton<T> {
+        let grid = Matrix::from_vec(1, cells.len(), cells.to_vec());
+        Automaton::new(grid, neighbourhood, boundary, rule)
+    }
+
+    pub fn grid(&self) -> &Matrix<T> {
+        &self.current
+    }
+
+    /// The world coordinates (y, x) of cell (0, 0) in the grid
+    pub fn origin(&self) -> (i64, i64) {
+        self.origin
+    }
+
+    /// Advance the automaton by a single generation
+    pub fn step(&mut self) {
+        if let Boundary::Expand(background) = self.boundary {
+            self.expand(background);
+        }
+
+        let mut neighbours = Vec::with_capacity(self.neighbourhood.offsets.len());
+
+        for y in 0..self.current.height {
+            for x in 0..self.current.width {
+                neighbours.clear();
+                for offset in &self.neighbourhood.offsets {
+                    neighbours.push(self.get_relative((y, x), *offset));
+                }
+                let value = (self.rule)(self.current[(y, x)], &neighbours);
+                self.next[(y, x)] = value;
+            }
+        }
+
+        mem::swap(&mut self.current, &mut self.next);
+        self.generation += 1;
+    }
+
+    /// Get the value of a cell relative to a position in the grid, taking the boundary into account
+    fn get_relative(&self, pos: (usize, usize), offset: (i32, i32)) -> T {
+        let (height, width) = (self.current.height as i64, self.current.width as i64);
+        let y = pos.0 as i64 + offset.0 as i64;
+        let x = pos.1 as i64 + offset.1 as i64;
+
+        if y >= 0 && y < height && x >= 0 && x < width {
+            return self.current[(y as usize, x as usize)];
+        }
+
+        match self.boundary {
+            Boundary::Constant(value) | Boundary::Expand(value) => value,
+            Boundary::Wrap => {
+                self.current[(y.rem_euclid(height) as usize, x.rem_euclid(width) as usize)]
+            }
+        }
+    }
+
+    /// Grow the grid so that there is enough background around every other cell that nothing can
+    /// appear outside of it in the next step.
+    fn expand(&mut self, background: T) {
+        let (height, width) = (self.current.height, self.current.width);
+
+        let mut bounds: Option<(usize, usize, usize, usize)> = None;
+        for y in 0..height {
+            for x in 0..width {
+                if self.current[(y, x)] != background {
+                    bounds = Some(match bounds {
+                        None => (y, y, x, x),
+                        Some((y0, y1, x0, x1)) => (y0.min(y), y1.max(y), x0.min(x), x1.max(x))
+                    });
+                }
+            }
+        }
+
+        let (min_y, max_y, min_x, max_x) = match bounds {
+            Some(b) => b,
+            None => return
+        };
+
+        let (ry, rx) = self.neighbourhood.reach();
+        let top = ry.saturating_sub(min_y);
+        let bottom = ry.saturating_sub(height - 1 - max_y);
+        let left = rx.saturating_sub(min_x);
+        let right = rx.saturating_sub(width - 1 - max_x);
+
+        if top + bottom + left + right == 0 {
+            return;
+        }
+
+        let mut grown = Matrix::new(height + top + bottom, width + left + right, background);
+        for y in 0..height {
+            for x in 0..width {
+                grown[(y + top, x + left)] = self.current[(y, x)];
+            }
+        }
+
+        self.next = grown.clone();
+        self.current = grown;
+        self.origin = (self.origin.0 - top as i64, self.origin.1 - left as i64);
+    }
+}
+
+impl<T> Automaton<T> where T: Copy + PartialEq + Eq + Hash {
+    /// Advance the automaton by a number of generations and predict the value of a metric of the
+    /// state after the last generation. A shortcut is taken when the evolution of the metric can be
+    /// predicted, in which case the automaton is left at the generation where that was detected.
+    /// Steps in the evidence are relative to the generation that the automaton started at.
+    pub fn advance(
+        &mut self,
+        generations: usize,
+        shortcut: Shortcut,
+        metric: impl Fn(&Automaton<T>) -> i64,
+    ) -> Prediction<i64> {
+        // The metric for every generation since the start
+        let mut metrics = vec![metric(self)];
+        let mut detector = CycleDetector::new();
+
+        if shortcut == Shortcut::Cycle {
+            detector.push((self.origin, self.current.clone()));
+        }
+
+        for g in 1..=generations {
+            self.step();
+            metrics.push(metric(self));
+
+            if g == generations {
+                break;
+            }
+
+            match shortcut {
+                Shortcut::Cycle => {
+                    if let Some(cycle) = detector.push((self.origin, self.current.clone())) {
+                        // The same state occurred before, so everything from here on repeats
+                        return Prediction {
+                            value: metrics[cycle.equivalent_step(generations)],
+                            evidence: Evidence::Cycle(cycle),
+                        };
+                    }
+                }
+                Shortcut::LinearGrowth { repeats } => {
+                    if let Some(prediction) = extrapolate_linear(&metrics, generations, repeats) {
+                        return prediction;
+                    }
+                }
+            }
+        }
+
+        Prediction { value: metrics[generations], evidence: Evidence::Simulated }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use utils::cycle::Cycle;
+
+    use super::*;
+
+    fn life(cell: bool, neighbours: &[bool]) -> bool {
+        let alive = neighbours.iter().filter(|n| **n).count();
+        alive == 3 || (cell && alive == 2)
+    }
+
+    fn count_alive(automaton: &Automaton<bool>) -> i64 {
+        automaton.grid().rows()
+            .map(|row| row.into_iter().filter(|c| **c).count() as i64)
+            .sum()
+    }
+
+    #[test]
+    fn test_blinker_cycle() {
+        let mut grid = Matrix::new(5, 5, false);
+        grid[(2, 1)] = true;
+        grid[(2, 2)] = true;
+        grid[(2, 3)] = true;
+
+        let mut automaton = Automaton::new(
+            grid, Neighbourhood::moore(1), Boundary::Constant(false), life,
+        );
+
+        automaton.step();
+        assert!(automaton.grid()[(1, 2)] && automaton.grid()[(3, 2)]);
+        assert!(!automaton.grid()[(2, 1)]);
+
+        let result = automaton.advance(1_000_001, Shortcut::Cycle, count_alive);
+        assert_eq!(result.value, 3);
+        assert_eq!(result.evidence, Evidence::Cycle(Cycle { start: 0, period: 2 }));
+    }
+
+    #[test]
+    fn test_wrap() {
+        // A glider on a torus comes back to its starting state after 4 * size generations
+        let mut grid = Matrix::new(6, 6, false);
+        for pos in &[(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)] {
+            grid[*pos] = true;
+        }
+        let initial = grid.clone();
+
+        let mut automaton = Automaton::new(grid, Neighbourhood::moore(1), Boundary::Wrap, life);
+        for _ in 0..24 {
+            automaton.step();
+        }
+        assert_eq!(automaton.grid(), &initial);
+    }
+
+    #[test]
+    fn test_expand_linear_growth() {
+        // Every cell becomes alive if it or its left neighbour is, so the row grows to the right
+        let mut automaton = Automaton::from_row(
+            &[true, false],
+            Neighbourhood::new(vec![(0, -1)]),
+            Boundary::Expand(false),
+            |cell, n| cell || n[0],
+        );
+
+        let result = automaton.advance(1000, Shortcut::LinearGrowth { repeats: 3 }, count_alive);
+        assert_eq!(result.value, 1001);
+        assert_eq!(result.evidence, Evidence::Linear { start: 0, delta: 1, repeats: 3 });
+        assert_eq!(automaton.origin(), (0, -1));
+    }
+
+    #[test]
+    fn test_empty_row() {
+        for boundary in [Boundary::Constant(true), Boundary::Wrap, Boundary::Expand(false)] {
+            let mut automaton = Automaton::from_row(&[], Neighbourhood::window(1), boundary, |_, n| n[0]);
+            automaton.step();
+            assert_eq!(automaton.grid().width, 0);
+            assert_eq!(count_alive(&automaton), 0);
+        }
+    }
+
+    #[test]
+    fn test_neighbourhoods() {
+        assert_eq!(Neighbourhood::moore(1).offsets().len(), 8);
+        assert_eq!(Neighbourhood::von_neumann(1).offsets(), &[(-1, 0), (0, -1), (0, 1), (1, 0)]);
+        assert_eq!(Neighbourhood::window(2).offsets(), &[(0, -2), (0, -1), (0, 0), (0, 1), (0, 2)]);
+    }
+}
@@ -1,9 +1,17 @@
+use std::fmt::Debug;
+use std::fmt::Error;
+use std::fmt::Formatter;
+use std::iter::FromIterator;
 use std::mem;
 
 /// A circular linked list
 pub struct CircularList<T> {
     nodes: Vec<Node<T>>,
     cursor: Option<usize>,
+    len: usize,
+    // Slots of removed nodes, which are reused by later inserts
+    free: Vec<usize>,
+    index: Option<Index>,
 }
 
 /// A node in the circular linked list
@@ -15,19 +23,59 @@ struct Node<T> {
 
 impl<T> CircularList<T> {
     pub fn new() -> CircularList<T> {
-        CircularList { nodes: Vec::new(), cursor: None }
+        CircularList::with_capacity(0)
     }
 
     pub fn with_capacity(capacity: usize) -> CircularList<T> {
         CircularList {
             nodes: Vec::with_capacity(capacity),
             cursor: None,
+            len: 0,
+            free: vec![],
+            index: None,
         }
     }
 
+    /// Keep track of the position of every value, so that seeking any number of steps and getting
+    /// the position of the current value take O(log n) time instead of O(n), while inserting and
+    /// removing get slower by the same amount. Positions are counted from the current value at the
+    /// time this is called, which becomes the first value of the list.
+    pub fn enable_index(&mut self) {
+        let mut index = Index::new();
+        if let Some(cur) = self.cursor {
+            index.insert(cur, None);
+            let mut node = cur;
+            while self.nodes[node].next != cur {
+                index.insert(self.nodes[node].next, Some(node));
+                node = self.nodes[node].next;
+            }
+        }
+        self.index = Some(index);
+    }
+
+    /// Get the position of the current value, counting from the first value in the list. Only
+    /// available when the index is enabled.
+    #[allow(dead_code)]
+    pub fn index_of_cursor(&self) -> Option<usize> {
+        let index = self.index.as_ref().expect("The list has no index");
+        self.cursor.map(|cur| index.position(cur))
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     /// Insert a value after the current value. This value becomes the new current value.
     pub fn insert(&mut self, value: T) {
-        let i = self.nodes.len();
+        let i = match self.free.last() {
+            Some(&i) => i,
+            None => self.nodes.len()
+        };
 
         let (prev, next) = match self.cursor {
             Some(cur) => {
@@ -46,8 +94,17 @@ impl<T> CircularList<T> {
             }
         };
 
-        self.nodes.push(Node { value: Some(value), prev, next });
+        let node = Node { value: Some(value), prev, next };
+        if self.free.pop().is_some() {
+            self.nodes[i] = node;
+        } else {
+            self.nodes.push(node);
+        }
+        if let Some(index) = &mut self.index {
+            index.insert(i, self.cursor);
+        }
         self.cursor = Some(i);
+        self.len += 1;
     }
 
     /// Remove the current value, and make the next value the current one
@@ -55,6 +112,10 @@ impl<T> CircularList<T> {
     /// If no current value is present, returns None
     pub fn remove(&mut self) -> Option<T> {
         self.cursor.map(|cur| {
+            if let Some(index) = &mut self.index {
+                index.remove(cur);
+            }
+
             let mut removed = Node { value: None, prev: 0, next: 0 };
             mem::swap(&mut removed, &mut self.nodes[cur]);
 
@@ -68,6 +129,8 @@ impl<T> CircularList<T> {
                 self.nodes[removed.prev].next = removed.next;
                 Some(removed.next)
             };
+            self.free.push(cur);
+            self.len -= 1;
 
             removed.value.unwrap()
         })
@@ -76,6 +139,27 @@ impl<T> CircularList<T> {
     /// Traverse the list clockwise (positive value) or counter-clockwise (negative value) for a
     /// number of steps
     pub fn seek(&mut self, offset: isize) -> &mut Self {
+        if self.len == 0 {
+            return self;
+        }
+
+        // Going around the whole list changes nothing, so only go the shortest way to the target
+        let len = self.len as isize;
+        let steps = offset.rem_euclid(len);
+        let offset = if steps > len / 2 { steps - len } else { steps };
+
+        if let (Some(index), Some(cur)) = (&self.index, self.cursor) {
+            // Short distances are still quicker to walk
+            if offset.abs() > 16 {
+                let target = (index.position(cur) + steps as usize) % self.len;
+                self.cursor = Some(index.at(target));
+                return self;
+            }
+        }
+        self.walk(offset)
+    }
+
+    fn walk(&mut self, offset: isize) -> &mut Self {
         for _ in 0..offset {
             self.next();
         }
@@ -99,18 +183,276 @@ impl<T> CircularList<T> {
 
     /// Get the current value, if any is present, otherwise None
     pub fn get(&self) -> Option<&T> {
-        match self.cursor {
-            Some(cur) => {
-                match &self.nodes[cur].value {
-                    Some(v) => Some(v),
-                    None => None
-                }
+        self.cursor.and_then(|cur| self.nodes[cur].value.as_ref())
+    }
+
+    /// Get the value after the current one without moving, which is the current one itself if
+    /// it's the only one
+    #[allow(dead_code)]
+    pub fn peek_next(&self) -> Option<&T> {
+        self.cursor.and_then(|cur| self.nodes[self.nodes[cur].next].value.as_ref())
+    }
+
+    /// Get the value before the current one without moving
+    #[allow(dead_code)]
+    pub fn peek_prev(&self) -> Option<&T> {
+        self.cursor.and_then(|cur| self.nodes[self.nodes[cur].prev].value.as_ref())
+    }
+
+    /// Iterate over all values once, starting with the current one and moving forward
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            list: self,
+            node: self.cursor.unwrap_or(0),
+            remaining: self.len,
+        }
+    }
+}
+
+impl<T> Default for CircularList<T> {
+    fn default() -> Self {
+        CircularList::new()
+    }
+}
+
+impl<T> Debug for CircularList<T> where T: Debug {
+    /// Format the values starting with the current one
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T> Extend<T> for CircularList<T> {
+    /// Insert all values in order after the current one, so the last one becomes the current one
+    fn extend<I: IntoIterator<Item=T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for CircularList<T> {
+    /// Collect values into a list where the first one is the current one
+    fn from_iter<I: IntoIterator<Item=T>>(iter: I) -> Self {
+        let mut list = CircularList::new();
+        list.extend(iter);
+        list.next();
+        list
+    }
+}
+
+impl<'a, T> IntoIterator for &'a CircularList<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+const NIL: usize = usize::MAX;
+
+/// The order of the nodes in the list, kept as a treap over the node slots that is ordered by
+/// position implicitly, through the sizes of subtrees
+struct Index {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    parent: Vec<usize>,
+    size: Vec<usize>,
+    priority: Vec<u32>,
+    root: usize,
+    // State of the xorshift generator for priorities
+    seed: u32,
+}
+
+impl Index {
+    fn new() -> Index {
+        Index {
+            left: vec![],
+            right: vec![],
+            parent: vec![],
+            size: vec![],
+            priority: vec![],
+            root: NIL,
+            seed: 0x9e37_79b9,
+        }
+    }
+
+    fn size(&self, node: usize) -> usize {
+        if node == NIL { 0 } else { self.size[node] }
+    }
+
+    /// Add a node slot right after another one, or as the only one
+    fn insert(&mut self, node: usize, after: Option<usize>) {
+        if node >= self.size.len() {
+            let len = node + 1;
+            self.left.resize(len, NIL);
+            self.right.resize(len, NIL);
+            self.parent.resize(len, NIL);
+            self.size.resize(len, 1);
+            self.priority.resize(len, 0);
+        }
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.left[node] = NIL;
+        self.right[node] = NIL;
+        self.size[node] = 1;
+        self.priority[node] = self.seed;
+
+        let after = match after {
+            Some(after) => after,
+            None => {
+                self.parent[node] = NIL;
+                self.root = node;
+                return;
+            }
+        };
+
+        // The next position is the leftmost node of the right subtree, or just below it
+        if self.right[after] == NIL {
+            self.right[after] = node;
+            self.parent[node] = after;
+        } else {
+            let mut next = self.right[after];
+            while self.left[next] != NIL {
+                next = self.left[next];
+            }
+            self.left[next] = node;
+            self.parent[node] = next;
+        }
+        self.add_size(self.parent[node], 1);
+
+        while self.parent[node] != NIL && self.priority[node] > self.priority[self.parent[node]] {
+            self.rotate_up(node);
+        }
+    }
+
+    /// Remove a node slot by rotating it down until it's a leaf
+    fn remove(&mut self, node: usize) {
+        loop {
+            let (left, right) = (self.left[node], self.right[node]);
+            let child = match (left, right) {
+                (NIL, NIL) => break,
+                (NIL, _) => right,
+                (_, NIL) => left,
+                _ => if self.priority[left] > self.priority[right] { left } else { right }
+            };
+            self.rotate_up(child);
+        }
+
+        let parent = self.parent[node];
+        if parent == NIL {
+            self.root = NIL;
+        } else {
+            if self.left[parent] == node {
+                self.left[parent] = NIL;
+            } else {
+                self.right[parent] = NIL;
+            }
+            self.add_size(parent, -1);
+        }
+    }
+
+    /// Change the size of a node and all its ancestors
+    fn add_size(&mut self, node: usize, delta: isize) {
+        let mut node = node;
+        while node != NIL {
+            self.size[node] = (self.size[node] as isize + delta) as usize;
+            node = self.parent[node];
+        }
+    }
+
+    /// Swap a node with its parent, keeping the order of positions the same
+    fn rotate_up(&mut self, node: usize) {
+        let parent = self.parent[node];
+        let grandparent = self.parent[parent];
+
+        if self.left[parent] == node {
+            let moved = self.right[node];
+            self.left[parent] = moved;
+            self.right[node] = parent;
+            if moved != NIL {
+                self.parent[moved] = parent;
+            }
+        } else {
+            let moved = self.left[node];
+            self.right[parent] = moved;
+            self.left[node] = parent;
+            if moved != NIL {
+                self.parent[moved] = parent;
+            }
+        }
+        self.parent[parent] = node;
+        self.parent[node] = grandparent;
+        self.size[node] = self.size[parent];
+        self.size[parent] = 1 + self.size(self.left[parent]) + self.size(self.right[parent]);
+
+        if grandparent == NIL {
+            self.root = node;
+        } else if self.left[grandparent] == parent {
+            self.left[grandparent] = node;
+        } else {
+            self.right[grandparent] = node;
+        }
+    }
+
+    /// Get the position of a node slot
+    fn position(&self, node: usize) -> usize {
+        let mut position = self.size(self.left[node]);
+        let mut node = node;
+        while self.parent[node] != NIL {
+            let parent = self.parent[node];
+            if self.right[parent] == node {
+                position += self.size(self.left[parent]) + 1;
+            }
+            node = parent;
+        }
+        position
+    }
+
+    /// Get the node slot at a position
+    fn at(&self, position: usize) -> usize {
+        let mut node = self.root;
+        let mut position = position;
+        loop {
+            let left_size = self.size(self.left[node]);
+            if position < left_size {
+                node = self.left[node];
+            } else if position == left_size {
+                return node;
+            } else {
+                position -= left_size + 1;
+                node = self.right[node];
             }
-            None => None
         }
     }
 }
 
+pub struct Iter<'a, T: 'a> {
+    list: &'a CircularList<T>,
+    node: usize,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = &self.list.nodes[self.node];
+        self.node = node.next;
+        self.remaining -= 1;
+        node.value.as_ref()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -142,4 +484,81 @@ mod test {
         circle.prev(); // [1, _3_]
         assert_eq!(circle.get(), Some(&3));
     }
+
+    #[test]
+    fn test_collection() {
+        let mut circle: CircularList<_> = (1..=4).collect(); // [_1_, 2, 3, 4]
+        assert_eq!(circle.len(), 4);
+        assert_eq!(format!("{:?}", circle), "[1, 2, 3, 4]");
+        assert_eq!(circle.peek_next(), Some(&2));
+        assert_eq!(circle.peek_prev(), Some(&4));
+
+        circle.seek(2).extend(vec![5, 6]); // [1, 2, 3, 5, _6_, 4]
+        assert_eq!(circle.iter().cloned().collect::<Vec<_>>(), vec![6, 4, 1, 2, 3, 5]);
+
+        while circle.remove().is_some() {}
+        assert!(circle.is_empty());
+        assert_eq!(circle.iter().next(), None);
+        assert_eq!(circle.peek_next(), None);
+    }
+
+    #[test]
+    fn test_slot_reuse() {
+        let mut circle = CircularList::new();
+        circle.insert(0);
+        for i in 1..1000 {
+            circle.insert(i);
+            circle.prev().remove();
+        }
+        assert_eq!(circle.len(), 1);
+        assert_eq!(circle.get(), Some(&999));
+        assert_eq!(circle.nodes.len(), 2);
+    }
+
+    #[test]
+    fn test_index() {
+        let mut circle: CircularList<_> = (0..10).collect();
+        circle.seek(3).enable_index();
+        assert_eq!(circle.index_of_cursor(), Some(0));
+        assert_eq!(circle.seek(-4).get(), Some(&9));
+        assert_eq!(circle.index_of_cursor(), Some(6));
+
+        // Compare random operations with large jumps against a plain list
+        let mut plain: CircularList<_> = (0..10).collect();
+        plain.seek(-1);
+        let mut seed = 12345u64;
+        for i in 10..3000 {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let offset = (seed >> 33) as isize % 2001 - 1000;
+            circle.seek(offset);
+            plain.seek(offset);
+            if i % 3 == 0 {
+                assert_eq!(circle.remove(), plain.remove());
+            } else {
+                circle.insert(i);
+                plain.insert(i);
+            }
+            assert_eq!(circle.get(), plain.get());
+        }
+        assert_eq!(circle.len(), plain.len());
+        assert_eq!(format!("{:?}", circle), format!("{:?}", plain));
+    }
+
+    #[test]
+    fn test_large_offsets() {
+        let mut indexed: CircularList<i128> = (0..100).collect();
+        indexed.enable_index();
+        let mut plain: CircularList<i128> = (0..100).collect();
+
+        let mut expected = 0i128;
+        for offset in [100, 250, -250, 117, -1001, isize::MAX, isize::MIN, isize::MIN + 1] {
+            expected = (expected + offset as i128).rem_euclid(100);
+            assert_eq!(indexed.seek(offset).get(), Some(&expected));
+            assert_eq!(plain.seek(offset).get(), Some(&expected));
+        }
+        assert_eq!(indexed.index_of_cursor(), Some(expected as usize));
+
+        let mut empty: CircularList<usize> = CircularList::new();
+        assert_eq!(empty.seek(isize::MIN).get(), None);
+    }
 }
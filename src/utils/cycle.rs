@@ -0,0 +1,295 @@
+//! Tools for predicting far-away steps of sequences that eventually repeat or grow linearly.
+//! Every prediction comes with the evidence it was based on, so callers can judge how much to
+//! trust it.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A cycle in a sequence: the first step that is part of the cycle and the length of the cycle
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Cycle {
+    pub start: usize,
+    pub period: usize,
+}
+
+impl Cycle {
+    /// Get the earliest step that has the same value as the given step
+    pub fn equivalent_step(&self, step: usize) -> usize {
+        if step < self.start {
+            step
+        } else {
+            self.start + (step - self.start) % self.period
+        }
+    }
+}
+
+/// What a prediction was based on
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Evidence {
+    /// The requested step was reached directly, nothing had to be predicted
+    Simulated,
+    /// A state was seen again. As long as every state only depends on the previous one, this is
+    /// proof that the sequence repeats forever.
+    Cycle(Cycle),
+    /// The end of a history of values repeats with a period, which was observed a number of times
+    /// in a row. This is a heuristic; the underlying state might not actually repeat.
+    Period { cycle: Cycle, repeats: usize },
+    /// The end of a history of values grew by the same delta a number of steps in a row. This is a
+    /// heuristic as well.
+    Linear { start: usize, delta: i64, repeats: usize },
+}
+
+/// The predicted value at some step, along with the evidence used to predict it
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Prediction<V> {
+    pub value: V,
+    pub evidence: Evidence,
+}
+
+/// Find the cycle in the sequence starting at `initial` using Floyd's tortoise and hare algorithm.
+/// Uses constant memory, but the sequence must be deterministic and eventually repeat.
+pub fn floyd<S>(initial: S, step: impl Fn(&S) -> S) -> Cycle where S: Eq + Clone {
+    // Find a step that's a multiple of the period, by letting the hare go twice as fast
+    let mut tortoise = step(&initial);
+    let mut hare = step(&step(&initial));
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&step(&hare));
+    }
+
+    // The hare is now a multiple of the period ahead, so moving both at the same speed from the
+    // start, they meet at the start of the cycle.
+    let mut start = 0;
+    tortoise = initial;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+
+    let mut period = 1;
+    hare = step(&tortoise);
+    while tortoise != hare {
+        hare = step(&hare);
+        period += 1;
+    }
+
+    Cycle { start, period }
+}
+
+/// Find the cycle in the sequence starting at `initial` using Brent's algorithm. Uses constant
+/// memory and fewer steps than Floyd's algorithm, with the same requirements.
+pub fn brent<S>(initial: S, step: impl Fn(&S) -> S) -> Cycle where S: Eq + Clone {
+    // Find the period by teleporting the tortoise to the hare at every power of two
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = step(&initial);
+    while tortoise != hare {
+        if power == period {
+            tortoise = hare.clone();
+            power *= 2;
+            period = 0;
+        }
+        hare = step(&hare);
+        period += 1;
+    }
+
+    // Start the hare one period ahead of the tortoise, and they will meet at the start of the cycle
+    let mut start = 0;
+    tortoise = initial.clone();
+    hare = initial;
+    for _ in 0..period {
+        hare = step(&hare);
+    }
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+
+    Cycle { start, period }
+}
+
+/// Predict the state of a sequence after `n` steps, using Brent's algorithm to skip over repeats
+pub fn predict_state<S>(initial: S, step: impl Fn(&S) -> S, n: usize) -> Prediction<S>
+    where S: Eq + Clone {
+    let cycle = brent(initial.clone(), &step);
+    let equivalent = cycle.equivalent_step(n);
+
+    let mut value = initial;
+    for _ in 0..equivalent {
+        value = step(&value);
+    }
+
+    let evidence = if equivalent == n { Evidence::Simulated } else { Evidence::Cycle(cycle) };
+    Prediction { value, evidence }
+}
+
+/// Detects the first repeated state in a sequence that is fed to it one step at a time. Uses memory
+/// for every distinct state, but works without being able to restart the sequence.
+pub struct CycleDetector<S> {
+    seen: HashMap<S, usize>,
+    steps: usize,
+}
+
+impl<S> CycleDetector<S> where S: Eq + Hash {
+    pub fn new() -> CycleDetector<S> {
+        CycleDetector { seen: HashMap::new(), steps: 0 }
+    }
+
+    /// Record the next state of the sequence. Returns the cycle if this state was seen before.
+    pub fn push(&mut self, state: S) -> Option<Cycle> {
+        let step = self.steps;
+        self.steps += 1;
+
+        if let Some(&start) = self.seen.get(&state) {
+            return Some(Cycle { start, period: step - start });
+        }
+        self.seen.insert(state, step);
+        None
+    }
+}
+
+impl<S> Default for CycleDetector<S> where S: Eq + Hash {
+    fn default() -> Self {
+        CycleDetector::new()
+    }
+}
+
+/// Find the shortest period with which the end of a history repeats itself at least `min_repeats`
+/// times, and the earliest step from where that period holds.
+pub fn find_period<V>(history: &[V], min_repeats: usize) -> Option<Cycle> where V: PartialEq {
+    let len = history.len();
+
+    for period in 1..=len / (min_repeats + 1) {
+        let tail = (min_repeats + 1) * period;
+        let periodic = (len - tail..len - period).all(|i| history[i] == history[i + period]);
+
+        if periodic {
+            // See how far back the period already holds
+            let mut start = len - tail;
+            while start > 0 && history[start - 1] == history[start - 1 + period] {
+                start -= 1;
+            }
+            return Some(Cycle { start, period });
+        }
+    }
+    None
+}
+
+/// Predict the value at step `n` of a history that repeats itself at least `min_repeats` times
+pub fn predict_periodic<V>(history: &[V], n: usize, min_repeats: usize) -> Option<Prediction<V>>
+    where V: PartialEq + Clone {
+    if n < history.len() {
+        return Some(Prediction { value: history[n].clone(), evidence: Evidence::Simulated });
+    }
+
+    find_period(history, min_repeats).map(|cycle| {
+        Prediction {
+            value: history[cycle.equivalent_step(n)].clone(),
+            evidence: Evidence::Period {
+                cycle,
+                repeats: (history.len() - cycle.start) / cycle.period - 1,
+            },
+        }
+    })
+}
+
+/// Predict the value at step `n` of a history that ended by growing with the same delta for at least
+/// `min_repeats` steps in a row
+pub fn extrapolate_linear(history: &[i64], n: usize, min_repeats: usize) -> Option<Prediction<i64>> {
+    let len = history.len();
+    if n < len {
+        return Some(Prediction { value: history[n], evidence: Evidence::Simulated });
+    }
+    if len < 2 {
+        return None;
+    }
+
+    let delta = history[len - 1] - history[len - 2];
+    let mut start = len - 2;
+    while start > 0 && history[start] - history[start - 1] == delta {
+        start -= 1;
+    }
+
+    let repeats = len - 1 - start;
+    if repeats < min_repeats {
+        return None;
+    }
+
+    Some(Prediction {
+        value: history[len - 1] + (n - (len - 1)) as i64 * delta,
+        evidence: Evidence::Linear { start, delta, repeats },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 3 -> 10 -> 101 -> 1 -> 2 -> 5 -> 26 -> 677 -> 598 -> 3 -> ...
+    fn next(x: &u32) -> u32 {
+        (x * x + 1) % 1000
+    }
+
+    #[test]
+    fn test_floyd_and_brent() {
+        let mut detector = CycleDetector::new();
+        let mut x = 7;
+        let expected = loop {
+            if let Some(cycle) = detector.push(x) {
+                break cycle;
+            }
+            x = next(&x);
+        };
+
+        assert_eq!(floyd(7, next), expected);
+        assert_eq!(brent(7, next), expected);
+        assert_eq!(brent(0, |x: &u32| (x + 1) % 5), Cycle { start: 0, period: 5 });
+    }
+
+    #[test]
+    fn test_predict_state() {
+        let step = |x: &u32| if *x < 10 { x + 1 } else { 7 };
+        // 0, 1, ..., 10, 7, 8, 9, 10, 7, ...
+        assert_eq!(
+            predict_state(0, step, 5),
+            Prediction { value: 5, evidence: Evidence::Simulated }
+        );
+        assert_eq!(
+            predict_state(0, step, 1_000_000),
+            Prediction { value: 8, evidence: Evidence::Cycle(Cycle { start: 7, period: 4 }) }
+        );
+    }
+
+    #[test]
+    fn test_predict_periodic() {
+        let history = vec![5, 4, 1, 2, 3, 1, 2, 3, 1, 2];
+        assert_eq!(find_period(&history, 1), Some(Cycle { start: 2, period: 3 }));
+        assert_eq!(find_period(&history, 2), None);
+
+        assert_eq!(
+            predict_periodic(&history, 100, 1),
+            Some(Prediction {
+                value: 3,
+                evidence: Evidence::Period { cycle: Cycle { start: 2, period: 3 }, repeats: 1 },
+            })
+        );
+        assert_eq!(predict_periodic(&history, 100, 2), None);
+    }
+
+    #[test]
+    fn test_extrapolate_linear() {
+        let history = vec![0, 7, 3, 5, 7, 9, 11];
+        assert_eq!(
+            extrapolate_linear(&history, 100, 4),
+            Some(Prediction { value: 199, evidence: Evidence::Linear { start: 2, delta: 2, repeats: 4 } })
+        );
+        assert_eq!(extrapolate_linear(&history, 100, 5), None);
+        assert_eq!(
+            extrapolate_linear(&history, 1, 5),
+            Some(Prediction { value: 7, evidence: Evidence::Simulated })
+        );
+    }
+}
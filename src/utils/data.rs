@@ -4,10 +4,7 @@ use std::path::PathBuf;
 
 /// Load the raw string contents from one of the puzzle input files
 pub fn load_data(name: &str) -> String {
-    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    path.push(format!("data/{}", name));
-
-    let mut file = File::open(path).unwrap();
+    let mut file = open_data(name);
     let mut contents = String::new();
 
     file.read_to_string(&mut contents).unwrap();
@@ -15,6 +12,14 @@ pub fn load_data(name: &str) -> String {
     contents
 }
 
+/// Open one of the puzzle input files, to read it as a stream
+pub fn open_data(name: &str) -> File {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push(format!("data/{}", name));
+
+    File::open(path).unwrap()
+}
+
 pub fn non_empty_lines(input: String) -> Vec<String> {
     input.split("\n")
         .into_iter()
@@ -9,7 +9,7 @@ use std::fmt::Write;
 use std::ops::Index;
 use std::ops::IndexMut;
 
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Eq, PartialEq, Hash)]
 pub struct Matrix<T> {
     buf: Vec<T>,
     pub height: usize,
@@ -28,6 +28,12 @@ impl<T> Matrix<T> where T: Copy {
 }
 
 impl<T> Matrix<T> {
+    /// Create a matrix of a custom shape from its values, row by row
+    pub fn from_vec(height: usize, width: usize, buf: Vec<T>) -> Matrix<T> {
+        assert_eq!(buf.len(), width * height);
+        Matrix { buf, height, width }
+    }
+
     fn index_of(&self, s: (usize, usize)) -> usize {
         assert!(s.0 < self.height);
         assert!(s.1 < self.width);
@@ -1,5 +1,10 @@
 pub mod data;
 pub mod matrix;
-pub mod misc;
 pub mod circular_list;
 pub mod elfcode;
+pub mod summed_area_table;
+pub mod automaton;
+pub mod cycle;
+pub mod search;
+pub mod scheduler;
+pub mod voronoi;
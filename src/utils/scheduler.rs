@@ -0,0 +1,279 @@
+//! Scheduling of tasks with dependencies over a number of workers. Tasks have string ids and their
+//! own durations, and a priority policy decides which ready task is started first.
+
+use std::cmp::Ordering;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Error;
+use std::fmt::Formatter;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Task {
+    pub id: String,
+    pub duration: u32,
+}
+
+/// Decides which of two ready tasks goes first, where the lesser one has the highest priority
+pub type Priority = fn(&Task, &Task) -> Ordering;
+
+/// Start tasks in order of their id
+pub fn alphabetical(a: &Task, b: &Task) -> Ordering {
+    a.id.cmp(&b.id)
+}
+
+/// Start the task that takes the least time first, by id on ties
+pub fn shortest_first(a: &Task, b: &Task) -> Ordering {
+    a.duration.cmp(&b.duration).then_with(|| alphabetical(a, b))
+}
+
+/// Start the task that takes the most time first, by id on ties
+pub fn longest_first(a: &Task, b: &Task) -> Ordering {
+    b.duration.cmp(&a.duration).then_with(|| alphabetical(a, b))
+}
+
+/// A set of tasks and the dependencies between them
+#[derive(Default)]
+pub struct Scheduler {
+    tasks: Vec<Task>,
+    indices: HashMap<String, usize>,
+    // For every task, the tasks that depend on it and the tasks it depends on
+    dependents: Vec<Vec<usize>>,
+    dependencies: Vec<Vec<usize>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler::default()
+    }
+
+    /// Add a task, or change its duration if it already exists
+    pub fn add_task(&mut self, id: &str, duration: u32) {
+        let i = self.get_or_add(id);
+        self.tasks[i].duration = duration;
+    }
+
+    /// Add a dependency between two tasks. Tasks that don't exist yet are added with a duration
+    /// of 0.
+    pub fn add_dependency(&mut self, task: &str, depends_on: &str) {
+        let task = self.get_or_add(task);
+        let depends_on = self.get_or_add(depends_on);
+        self.dependents[depends_on].push(task);
+        self.dependencies[task].push(depends_on);
+    }
+
+    fn get_or_add(&mut self, id: &str) -> usize {
+        if let Some(i) = self.indices.get(id) {
+            return *i;
+        }
+        let i = self.tasks.len();
+        self.tasks.push(Task { id: String::from(id), duration: 0 });
+        self.indices.insert(String::from(id), i);
+        self.dependents.push(vec![]);
+        self.dependencies.push(vec![]);
+        i
+    }
+
+    /// Get the order in which a single worker would perform the tasks
+    pub fn order(&self, priority: Priority) -> Result<Vec<String>, CycleError> {
+        let schedule = self.run(1, priority)?;
+        Ok(schedule.entries.into_iter().map(|e| e.task).collect())
+    }
+
+    /// Simulate a number of workers that each start the ready task with the highest priority as
+    /// soon as they are idle. Idle workers with the lowest number get to pick first.
+    pub fn run(&self, workers: usize, priority: Priority) -> Result<Schedule, CycleError> {
+        assert!(workers > 0);
+
+        let mut waiting_for: Vec<usize> = self.dependencies.iter().map(|d| d.len()).collect();
+        let mut ready: Vec<usize> = (0..self.tasks.len()).filter(|i| waiting_for[*i] == 0).collect();
+        let mut idle: Vec<usize> = (0..workers).rev().collect();
+        // Running tasks as (end, worker, task), earliest end first
+        let mut running = BinaryHeap::new();
+
+        let mut entries = vec![];
+        let mut time = 0;
+
+        loop {
+            // Keep the ready tasks sorted with the highest priority at the end, so it can be popped
+            ready.sort_by(|a, b| priority(&self.tasks[*b], &self.tasks[*a]));
+            while !idle.is_empty() && !ready.is_empty() {
+                let worker = idle.pop().unwrap();
+                let task = ready.pop().unwrap();
+                let end = time + self.tasks[task].duration;
+                running.push(Reverse((end, worker, task)));
+                entries.push(Entry {
+                    worker,
+                    task: self.tasks[task].id.clone(),
+                    start: time,
+                    end,
+                });
+            }
+
+            // Finish every task that ends first
+            time = match running.peek() {
+                Some(Reverse((end, _, _))) => *end,
+                None => break
+            };
+            while let Some(Reverse((end, worker, task))) = running.peek().cloned() {
+                if end != time {
+                    break;
+                }
+                running.pop();
+                idle.push(worker);
+                for dependent in &self.dependents[task] {
+                    waiting_for[*dependent] -= 1;
+                    if waiting_for[*dependent] == 0 {
+                        ready.push(*dependent);
+                    }
+                }
+            }
+            idle.sort_by(|a, b| b.cmp(a));
+        }
+
+        if entries.len() < self.tasks.len() {
+            return Err(self.find_cycle(&waiting_for));
+        }
+
+        Ok(Schedule { entries, makespan: time })
+    }
+
+    /// Find a cycle among the tasks that never got ready. Following unfinished dependencies from
+    /// any such task must eventually lead into a cycle.
+    fn find_cycle(&self, waiting_for: &[usize]) -> CycleError {
+        let start = (0..self.tasks.len()).find(|i| waiting_for[*i] > 0).unwrap();
+
+        let mut path = vec![start];
+        let mut seen = HashMap::new();
+        seen.insert(start, 0);
+
+        loop {
+            let current = *path.last().unwrap();
+            let next = *self.dependencies[current].iter()
+                .find(|d| waiting_for[**d] > 0)
+                .unwrap();
+
+            if let Some(&pos) = seen.get(&next) {
+                // Report the cycle in the direction of the dependencies, ending where it starts
+                let mut cycle: Vec<_> = path[pos..].iter().rev()
+                    .map(|i| self.tasks[*i].id.clone())
+                    .collect();
+                cycle.push(cycle[0].clone());
+                return CycleError { cycle };
+            }
+
+            seen.insert(next, path.len());
+            path.push(next);
+        }
+    }
+}
+
+/// A task that was performed by a worker from start to end
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Entry {
+    pub worker: usize,
+    pub task: String,
+    pub start: u32,
+    pub end: u32,
+}
+
+/// All tasks in the order in which they were started, along with the time at which everything was
+/// finished
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Schedule {
+    pub entries: Vec<Entry>,
+    pub makespan: u32,
+}
+
+impl Display for Schedule {
+    /// Format the schedule as a Gantt-style timeline, with a line for every task
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        let id_width = self.entries.iter().map(|e| e.task.len()).max().unwrap_or(0).max(4);
+        writeln!(f, "Worker  {:<w$}  Start    End", "Task", w = id_width)?;
+        for entry in &self.entries {
+            writeln!(
+                f, "{:>6}  {:<w$}  {:>5}  {:>5}",
+                entry.worker + 1, entry.task, entry.start, entry.end, w = id_width
+            )?;
+        }
+        write!(f, "Finished at {}", self.makespan)
+    }
+}
+
+/// The dependencies contain a cycle, so the tasks on it can never be started
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CycleError {
+    /// The tasks on the cycle, where each one depends on the previous one, starting and ending
+    /// with the same task
+    pub cycle: Vec<String>,
+}
+
+impl Display for CycleError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(f, "Dependency cycle: {}", self.cycle.join(" -> "))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_scheduler() -> Scheduler {
+        let mut scheduler = Scheduler::new();
+        scheduler.add_task("fetch", 2);
+        scheduler.add_task("compile", 5);
+        scheduler.add_task("lint", 1);
+        scheduler.add_task("link", 2);
+        scheduler.add_task("test", 3);
+        scheduler.add_dependency("compile", "fetch");
+        scheduler.add_dependency("lint", "fetch");
+        scheduler.add_dependency("link", "compile");
+        scheduler.add_dependency("test", "link");
+        scheduler
+    }
+
+    #[test]
+    fn test_order() {
+        let scheduler = get_scheduler();
+        assert_eq!(
+            scheduler.order(alphabetical).unwrap(),
+            vec!["fetch", "compile", "link", "lint", "test"]
+        );
+        assert_eq!(
+            scheduler.order(longest_first).unwrap(),
+            vec!["fetch", "compile", "link", "test", "lint"]
+        );
+    }
+
+    #[test]
+    fn test_run() {
+        let schedule = get_scheduler().run(2, alphabetical).unwrap();
+        assert_eq!(schedule.makespan, 12);
+        assert_eq!(
+            schedule.entries[1..3],
+            [
+                Entry { worker: 0, task: String::from("compile"), start: 2, end: 7 },
+                Entry { worker: 1, task: String::from("lint"), start: 2, end: 3 },
+            ]
+        );
+
+        let expected = "Worker  Task     Start    End
+     1  fetch        0      2
+     1  compile      2      7
+     2  lint         2      3
+     1  link         7      9
+     1  test         9     12
+Finished at 12";
+        assert_eq!(schedule.to_string(), expected);
+    }
+
+    #[test]
+    fn test_cycle() {
+        let mut scheduler = get_scheduler();
+        scheduler.add_dependency("compile", "test");
+
+        let error = scheduler.run(2, alphabetical).unwrap_err();
+        assert_eq!(error.to_string(), "Dependency cycle: link -> test -> compile -> link");
+    }
+}
@@ -0,0 +1,272 @@
+//! Graph searches (BFS, Dijkstra and A*) over implicit graphs. A graph is defined by a closure that
+//! produces the neighbours of a node, so nodes can be anything hashable, such as coordinates or
+//! (coordinate, tool) states.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::hash::Hash;
+
+/// The outcome of a search: the distance to and the predecessor of every node that was settled,
+/// along with the goal node if one was reached.
+pub struct Search<N> {
+    distances: HashMap<N, usize>,
+    parents: HashMap<N, N>,
+    pub goal: Option<N>,
+}
+
+impl<N> Search<N> where N: Eq + Hash + Clone {
+    fn new() -> Search<N> {
+        Search { distances: HashMap::new(), parents: HashMap::new(), goal: None }
+    }
+
+    /// Get the shortest distance from the start to a node, if it was reached
+    pub fn distance(&self, node: &N) -> Option<usize> {
+        self.distances.get(node).cloned()
+    }
+
+    /// Get the shortest distances to all reached nodes
+    pub fn distances(&self) -> &HashMap<N, usize> {
+        &self.distances
+    }
+
+    /// Count the nodes that were reached, including the start
+    pub fn count_reachable(&self) -> usize {
+        self.distances.len()
+    }
+
+    /// Count the reached nodes that are at least a distance away from the start
+    pub fn count_at_least(&self, distance: usize) -> usize {
+        self.distances.values().filter(|d| **d >= distance).count()
+    }
+
+    /// Get the distance to the reached node that is furthest away from the start
+    pub fn max_distance(&self) -> Option<usize> {
+        self.distances.values().max().cloned()
+    }
+
+    /// Reconstruct a shortest path from the start to a node, including both ends
+    pub fn path_to(&self, node: &N) -> Option<Vec<N>> {
+        if !self.distances.contains_key(node) {
+            return None;
+        }
+
+        let mut path = vec![node.clone()];
+        while let Some(parent) = self.parents.get(path.last().unwrap()) {
+            path.push(parent.clone());
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Reconstruct the shortest path from the start to the goal, if a goal was reached
+    pub fn goal_path(&self) -> Option<Vec<N>> {
+        self.goal.as_ref().and_then(|g| self.path_to(g))
+    }
+}
+
+/// Explore every node reachable from the start, where every edge has a length of 1
+pub fn bfs<N, I>(start: N, neighbours: impl FnMut(&N) -> I) -> Search<N>
+    where N: Eq + Hash + Clone, I: IntoIterator<Item=N> {
+    bfs_to(start, neighbours, |_| false)
+}
+
+/// Breadth first search from the start until a goal node is reached, where every edge has a length
+/// of 1. Nodes are visited in the order in which the neighbours are produced.
+pub fn bfs_to<N, I>(
+    start: N,
+    mut neighbours: impl FnMut(&N) -> I,
+    mut is_goal: impl FnMut(&N) -> bool,
+) -> Search<N> where N: Eq + Hash + Clone, I: IntoIterator<Item=N> {
+    let mut search = Search::new();
+    search.distances.insert(start.clone(), 0);
+
+    let mut queue = VecDeque::new();
+    queue.push_back(start);
+
+    while let Some(node) = queue.pop_front() {
+        if is_goal(&node) {
+            search.goal = Some(node);
+            break;
+        }
+
+        let next_dist = search.distances[&node] + 1;
+        for next in neighbours(&node) {
+            if !search.distances.contains_key(&next) {
+                search.distances.insert(next.clone(), next_dist);
+                search.parents.insert(next.clone(), node.clone());
+                queue.push_back(next);
+            }
+        }
+    }
+
+    search
+}
+
+/// Dijkstra's algorithm from the start until a goal node is reached. The neighbour closure produces
+/// (node, edge length) pairs.
+pub fn dijkstra<N, I>(
+    start: N,
+    neighbours: impl FnMut(&N) -> I,
+    is_goal: impl FnMut(&N) -> bool,
+) -> Search<N> where N: Eq + Hash + Clone, I: IntoIterator<Item=(N, usize)> {
+    astar(start, neighbours, |_| 0, is_goal)
+}
+
+/// A* search from the start until a goal node is reached. The neighbour closure produces
+/// (node, edge length) pairs. The heuristic must never overestimate the remaining distance to a goal
+/// and must be consistent, otherwise the found distances might not be the shortest.
+pub fn astar<N, I>(
+    start: N,
+    mut neighbours: impl FnMut(&N) -> I,
+    heuristic: impl Fn(&N) -> usize,
+    mut is_goal: impl FnMut(&N) -> bool,
+) -> Search<N> where N: Eq + Hash + Clone, I: IntoIterator<Item=(N, usize)> {
+    let mut search = Search::new();
+
+    // The best known distance of every node that was encountered but possibly not yet settled
+    let mut best = HashMap::new();
+    best.insert(start.clone(), 0);
+
+    let mut pending = BinaryHeap::new();
+    pending.push(Pending { priority: heuristic(&start), distance: 0, node: start, parent: None });
+
+    while let Some(Pending { distance, node, parent, .. }) = pending.pop() {
+        if search.distances.contains_key(&node) {
+            // Already settled through a shorter path
+            continue;
+        }
+
+        search.distances.insert(node.clone(), distance);
+        if let Some(p) = parent {
+            search.parents.insert(node.clone(), p);
+        }
+
+        if is_goal(&node) {
+            search.goal = Some(node);
+            break;
+        }
+
+        for (next, length) in neighbours(&node) {
+            let next_dist = distance + length;
+            let is_shorter = match best.get(&next) {
+                Some(d) => next_dist < *d,
+                None => true
+            };
+            if is_shorter && !search.distances.contains_key(&next) {
+                best.insert(next.clone(), next_dist);
+                pending.push(Pending {
+                    priority: next_dist + heuristic(&next),
+                    distance: next_dist,
+                    node: next,
+                    parent: Some(node.clone()),
+                });
+            }
+        }
+    }
+
+    search
+}
+
+/// A node waiting in the priority queue. Ordered in reverse by priority so that the max-heap pops
+/// the lowest priority first.
+struct Pending<N> {
+    priority: usize,
+    distance: usize,
+    node: N,
+    parent: Option<N>,
+}
+
+impl<N> Ord for Pending<N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.cmp(&self.priority)
+    }
+}
+
+impl<N> PartialOrd for Pending<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N> PartialEq for Pending<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority
+    }
+}
+
+impl<N> Eq for Pending<N> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_maze() -> Vec<&'static str> {
+        vec![
+            "#######",
+            "#S..#.#",
+            "#.#.#.#",
+            "#.#...#",
+            "#...#G#",
+            "#######",
+        ]
+    }
+
+    fn maze_neighbours(maze: &[&str], pos: &(usize, usize)) -> Vec<(usize, usize)> {
+        let (y, x) = *pos;
+        vec![(y - 1, x), (y, x - 1), (y, x + 1), (y + 1, x)].into_iter()
+            .filter(|(y, x)| maze[*y].as_bytes()[*x] != b'#')
+            .collect()
+    }
+
+    #[test]
+    fn test_bfs() {
+        let maze = get_maze();
+        let search = bfs((1, 1), |p| maze_neighbours(&maze, p));
+
+        assert_eq!(search.count_reachable(), 15);
+        assert_eq!(search.distance(&(4, 5)), Some(7));
+        assert_eq!(search.distance(&(0, 0)), None);
+        assert_eq!(search.max_distance(), Some(8));
+        assert_eq!(search.count_at_least(6), 4);
+        assert_eq!(
+            search.path_to(&(3, 4)),
+            Some(vec![(1, 1), (1, 2), (1, 3), (2, 3), (3, 3), (3, 4)])
+        );
+    }
+
+    #[test]
+    fn test_bfs_to() {
+        let maze = get_maze();
+        let search = bfs_to((1, 1), |p| maze_neighbours(&maze, p), |p| *p == (1, 3));
+
+        assert_eq!(search.goal, Some((1, 3)));
+        assert_eq!(search.goal_path(), Some(vec![(1, 1), (1, 2), (1, 3)]));
+    }
+
+    #[test]
+    fn test_dijkstra_and_astar() {
+        // Going right costs 1, going down costs 10, or take a shortcut down at x = 3 for 2
+        let neighbours = |p: &(usize, usize)| {
+            let mut result = vec![];
+            if p.1 < 5 {
+                result.push(((p.0, p.1 + 1), 1));
+            }
+            if p.0 < 5 {
+                result.push(((p.0 + 1, p.1), if p.1 == 3 { 2 } else { 10 }));
+            }
+            result
+        };
+        let goal = (5, 5);
+
+        let search = dijkstra((0, 0), neighbours, |p| *p == goal);
+        assert_eq!(search.distance(&goal), Some(15));
+        assert_eq!(search.goal_path().unwrap().len(), 11);
+
+        let heuristic = |p: &(usize, usize)| (goal.0 - p.0) + (goal.1 - p.1);
+        let search = astar((0, 0), neighbours, heuristic, |p| *p == goal);
+        assert_eq!(search.distance(&goal), Some(15));
+        assert!(search.count_reachable() < 36);
+    }
+}
@@ -0,0 +1,107 @@
+//! Summed-area table (2d prefix sums) over a numeric Matrix. After an O(n) build, the sum of any
+//! rectangle can be queried in constant time.
+
+use std::ops::Add;
+use std::ops::Sub;
+
+use utils::matrix::Matrix;
+
+pub struct SummedAreaTable<T> {
+    // Padded with a zero row and column, so sums[(y, x)] is the sum of all source cells above and
+    // to the left of (y, x), exclusive.
+    sums: Matrix<T>,
+    pub height: usize,
+    pub width: usize,
+}
+
+impl<T> SummedAreaTable<T> where T: Copy + Default + Add<Output=T> + Sub<Output=T> {
+    /// Build the table from a source matrix
+    pub fn new(source: &Matrix<T>) -> SummedAreaTable<T> {
+        let (height, width) = (source.height, source.width);
+        let mut sums = Matrix::new(height + 1, width + 1, T::default());
+
+        for y in 0..height {
+            for x in 0..width {
+                sums[(y + 1, x + 1)] = source[(y, x)] + sums[(y, x + 1)] + sums[(y + 1, x)]
+                    - sums[(y, x)];
+            }
+        }
+
+        SummedAreaTable { sums, height, width }
+    }
+
+    /// Get the sum of the rectangle with its top left corner at (y, x) and a size of
+    /// (height, width)
+    pub fn sum(&self, top_left: (usize, usize), size: (usize, usize)) -> T {
+        let (y, x) = top_left;
+        let (h, w) = size;
+        assert!(y + h <= self.height);
+        assert!(x + w <= self.width);
+
+        self.sums[(y + h, x + w)] + self.sums[(y, x)] - self.sums[(y, x + w)] - self.sums[(y + h, x)]
+    }
+
+    /// Find the k×k window with the highest sum. Returns the (y, x) of its top left corner along
+    /// with the sum, or None if the window does not fit. On ties, the first window in (y, x) order
+    /// wins.
+    pub fn best_window(&self, k: usize) -> Option<((usize, usize), T)> where T: PartialOrd {
+        if k == 0 || k > self.height || k > self.width {
+            return None;
+        }
+
+        let mut best: Option<((usize, usize), T)> = None;
+        for y in 0..=self.height - k {
+            for x in 0..=self.width - k {
+                let total = self.sum((y, x), (k, k));
+                let is_better = match best {
+                    Some((_, b)) => total > b,
+                    None => true
+                };
+                if is_better {
+                    best = Some(((y, x), total));
+                }
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sum() {
+        let mut m = Matrix::new(3, 4, 0);
+        let mut v = 0;
+        for y in 0..3 {
+            for x in 0..4 {
+                v += 1;
+                m[(y, x)] = v;
+            }
+        }
+        // [[ 1,  2,  3,  4]
+        //  [ 5,  6,  7,  8]
+        //  [ 9, 10, 11, 12]]
+        let sat = SummedAreaTable::new(&m);
+
+        assert_eq!(sat.sum((0, 0), (3, 4)), 78);
+        assert_eq!(sat.sum((1, 1), (2, 2)), 34);
+        assert_eq!(sat.sum((2, 3), (1, 1)), 12);
+        assert_eq!(sat.sum((1, 2), (0, 2)), 0);
+    }
+
+    #[test]
+    fn test_best_window() {
+        let mut m = Matrix::new(3, 3, 0);
+        m[(0, 2)] = 5;
+        m[(1, 1)] = -1;
+        m[(2, 0)] = 5;
+        let sat = SummedAreaTable::new(&m);
+
+        assert_eq!(sat.best_window(1), Some(((0, 2), 5)));
+        assert_eq!(sat.best_window(2), Some(((0, 1), 4)));
+        assert_eq!(sat.best_window(3), Some(((0, 0), 9)));
+        assert_eq!(sat.best_window(4), None);
+    }
+}
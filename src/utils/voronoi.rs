@@ -0,0 +1,467 @@
+//! Partitions of the grid into regions of points that are closest to one of a number of sites,
+//! under a choice of distance metric. Points are (x, y) pairs, and points that are equally close to
+//! more than one site don't belong to any region.
+
+use std::collections::HashSet;
+use std::f64::consts::PI;
+
+use utils::matrix::Matrix;
+
+pub type Point = (i64, i64);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Metric {
+    Manhattan,
+    Chebyshev,
+    Euclidean,
+}
+
+impl Metric {
+    pub fn distance(self, a: Point, b: Point) -> f64 {
+        match self {
+            Metric::Euclidean => (self.rank(a, b) as f64).sqrt(),
+            _ => self.rank(a, b) as f64
+        }
+    }
+
+    /// An exact value that orders distances the same way as `distance` does
+    fn rank(self, a: Point, b: Point) -> i64 {
+        let (dx, dy) = ((a.0 - b.0).abs(), (a.1 - b.1).abs());
+        match self {
+            Metric::Manhattan => dx + dy,
+            Metric::Chebyshev => dx.max(dy),
+            Metric::Euclidean => dx * dx + dy * dy,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Region {
+    Finite(usize),
+    Infinite,
+}
+
+pub struct Voronoi {
+    sites: Vec<Point>,
+    metric: Metric,
+}
+
+impl Voronoi {
+    pub fn new(sites: Vec<Point>, metric: Metric) -> Voronoi {
+        assert!(!sites.is_empty());
+        Voronoi { sites, metric }
+    }
+
+    /// Get the index of the site that is closest to a point, or None on a tie
+    pub fn nearest(&self, p: Point) -> Option<usize> {
+        let mut best = None;
+        let mut best_rank = i64::MAX;
+        let mut tied = false;
+
+        for (i, site) in self.sites.iter().enumerate() {
+            let rank = self.metric.rank(p, *site);
+            if rank < best_rank {
+                best = Some(i);
+                best_rank = rank;
+                tied = false;
+            } else if rank == best_rank {
+                tied = true;
+            }
+        }
+
+        if tied { None } else { best }
+    }
+
+    /// Get the corners (min, max) of the smallest box that contains all sites
+    pub fn bounds(&self) -> (Point, Point) {
+        bounds(&self.sites)
+    }
+
+    /// Get the nearest site for every point from min to max inclusive, in a matrix of rows (y) and
+    /// columns (x) relative to min
+    pub fn partition(&self, min: Point, max: Point) -> Matrix<Option<usize>> {
+        let mut result = Matrix::new((max.1 - min.1 + 1) as usize, (max.0 - min.0 + 1) as usize, None);
+        for y in min.1..=max.1 {
+            for x in min.0..=max.0 {
+                result[((y - min.1) as usize, (x - min.0) as usize)] = self.nearest((x, y));
+            }
+        }
+        result
+    }
+
+    /// Get the region of every site, with the number of points in it when it's finite
+    pub fn regions(&self) -> Vec<Region> {
+        let infinite = self.infinite_regions();
+        let mut areas = vec![0; self.sites.len()];
+
+        if self.metric == Metric::Euclidean {
+            for (i, area) in areas.iter_mut().enumerate() {
+                if !infinite[i] {
+                    *area = self.euclidean_area(i);
+                }
+            }
+        } else {
+            let (min, max) = self.finite_extent();
+            for y in min.1..=max.1 {
+                for x in min.0..=max.0 {
+                    if let Some(i) = self.nearest((x, y)) {
+                        areas[i] += 1;
+                    }
+                }
+            }
+        }
+
+        areas.into_iter().zip(infinite)
+            .map(|(area, infinite)| if infinite { Region::Infinite } else { Region::Finite(area) })
+            .collect()
+    }
+
+    /// Count the points whose distances to all sites add up to less than the limit.
+    ///
+    /// For any point p and site c, n * d(p, c) <= total(p) + total(c) by the triangle inequality,
+    /// which bounds how far such points can be from the first site. The total distance is convex,
+    /// so on every row they form an interval around the row's lowest point.
+    pub fn total_distance_below(&self, limit: f64) -> usize {
+        let total = |p: Point| self.sites.iter()
+            .map(|s| self.metric.distance(p, *s))
+            .sum::<f64>();
+
+        let c = self.sites[0];
+        let reach = ((limit + total(c)) / self.sites.len() as f64).ceil() as i64;
+        let (left, right) = (c.0 - reach, c.0 + reach);
+
+        let mut count = 0;
+        for y in c.1 - reach..=c.1 + reach {
+            let row = |x| total((x, y));
+
+            // Find the first point from which the row stops decreasing
+            let (mut lo, mut hi) = (left, right);
+            while lo < hi {
+                let mid = lo + (hi - lo) / 2;
+                if row(mid + 1) < row(mid) { lo = mid + 1 } else { hi = mid }
+            }
+            let lowest = lo;
+            if row(lowest) >= limit {
+                continue;
+            }
+
+            // Find the first point below the limit on the left side, and the last on the right
+            let (mut lo, mut hi) = (left, lowest);
+            while lo < hi {
+                let mid = lo + (hi - lo) / 2;
+                if row(mid) < limit { hi = mid } else { lo = mid + 1 }
+            }
+            let start = lo;
+
+            let (mut lo, mut hi) = (lowest, right);
+            while lo < hi {
+                let mid = lo + (hi - lo + 1) / 2;
+                if row(mid) < limit { lo = mid } else { hi = mid - 1 }
+            }
+
+            count += (lo - start + 1) as usize;
+        }
+        count
+    }
+
+    /// Render the points from min to max inclusive in the puzzle's notation, with a letter per
+    /// region that is capitalized at the site itself and dots for ties
+    pub fn render(&self, min: Point, max: Point) -> String {
+        let partition = self.partition(min, max);
+        let mut result = String::new();
+        for (y, row) in partition.rows().enumerate() {
+            for (x, cell) in row.into_iter().enumerate() {
+                result.push(match *cell {
+                    Some(i) => {
+                        let letter = (b'a' + (i % 26) as u8) as char;
+                        let p = (min.0 + x as i64, min.1 + y as i64);
+                        if self.sites[i] == p { letter.to_ascii_uppercase() } else { letter }
+                    }
+                    None => '.'
+                });
+            }
+            result.push('\n');
+        }
+        result
+    }
+
+    /// Render the points from min to max inclusive as a binary PPM image, with a colour per region,
+    /// black for ties and white for the sites
+    pub fn render_ppm(&self, min: Point, max: Point) -> Vec<u8> {
+        let partition = self.partition(min, max);
+        let mut image = format!("P6\n{} {}\n255\n", partition.width, partition.height).into_bytes();
+        image.reserve(partition.width * partition.height * 3);
+
+        let sites: HashSet<_> = self.sites.iter().cloned().collect();
+        for (y, row) in partition.rows().enumerate() {
+            for (x, cell) in row.into_iter().enumerate() {
+                let p = (min.0 + x as i64, min.1 + y as i64);
+                image.extend_from_slice(&match *cell {
+                    _ if sites.contains(&p) => [255, 255, 255],
+                    Some(i) => region_color(i),
+                    None => [0, 0, 0]
+                });
+            }
+        }
+        image
+    }
+
+    fn infinite_regions(&self) -> Vec<bool> {
+        match self.metric {
+            Metric::Manhattan => self.ring_owners(|x, y| (x, y), |u, v| Some((u, v)), 0),
+            // Chebyshev distance is half the Manhattan distance after a rotation by 45 degrees,
+            // which maps the grid onto the points with an even u + v
+            Metric::Chebyshev => self.ring_owners(
+                |x, y| (x + y, x - y),
+                |u, v| if (u + v) % 2 == 0 { Some(((u + v) / 2, (u - v) / 2)) } else { None },
+                1,
+            ),
+            Metric::Euclidean => self.hull_sites(),
+        }
+    }
+
+    /// Find the regions that contain a point on the ring around the bounding box of the sites, in
+    /// rotated coordinates where the metric is Manhattan. The ring is as thick as the margin, and
+    /// steps of 1 + margin stay on the grid.
+    ///
+    /// Stepping straight away from the box from a point on the ring adds the same distance to every
+    /// site, so the point's region is infinite. The other way around, stepping from any point
+    /// outside towards the box ends on the ring and keeps the same nearest site, so every infinite
+    /// region is found.
+    fn ring_owners(&self, rotate: fn(i64, i64) -> Point, unrotate: fn(i64, i64) -> Option<Point>,
+                   margin: i64) -> Vec<bool> {
+        let rotated: Vec<_> = self.sites.iter().map(|s| rotate(s.0, s.1)).collect();
+        let (min, max) = bounds(&rotated);
+        let (min, max) = ((min.0 - margin, min.1 - margin), (max.0 + margin, max.1 + margin));
+        let on_ring = |a, min, max| a <= min + margin || a >= max - margin;
+
+        let mut infinite = vec![false; self.sites.len()];
+        for u in min.0..=max.0 {
+            let vs: Vec<_> = if on_ring(u, min.0, max.0) {
+                (min.1..=max.1).collect()
+            } else {
+                (0..=margin).flat_map(|m| vec![min.1 + m, max.1 - m]).collect()
+            };
+            for v in vs {
+                if let Some(i) = unrotate(u, v).and_then(|p| self.nearest(p)) {
+                    infinite[i] = true;
+                }
+            }
+        }
+        infinite
+    }
+
+    /// Get the box (min, max) that contains every point of the finite regions, for the metrics
+    /// handled by `ring_owners`
+    fn finite_extent(&self) -> (Point, Point) {
+        let (min, max) = self.bounds();
+        if self.metric == Metric::Manhattan {
+            return (min, max);
+        }
+
+        // Convert the ring around the rotated bounding box back, with a bit of slack for rounding
+        let rotated: Vec<_> = self.sites.iter().map(|s| (s.0 + s.1, s.0 - s.1)).collect();
+        let (min, max) = bounds(&rotated);
+        (
+            ((min.0 + min.1) / 2 - 2, (min.0 - max.1) / 2 - 2),
+            ((max.0 + max.1) / 2 + 2, (max.0 - min.1) / 2 + 2),
+        )
+    }
+
+    /// Find the sites on the boundary of the convex hull of all sites, including those halfway an
+    /// edge. Under the Euclidean metric exactly those have an unbounded region, which contains the
+    /// grid points on a ray from the site away from the hull.
+    fn hull_sites(&self) -> Vec<bool> {
+        let mut points = self.sites.clone();
+        points.sort();
+        points.dedup();
+
+        let cross = |o: Point, a: Point, b: Point| {
+            (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+        };
+
+        // Monotone chain over both halves, only dropping points on a strictly clockwise turn so
+        // points along an edge are kept
+        let mut hull = HashSet::new();
+        for half in [points.clone(), points.into_iter().rev().collect()] {
+            let mut chain: Vec<Point> = vec![];
+            for p in half {
+                while chain.len() >= 2 && cross(chain[chain.len() - 2], chain[chain.len() - 1], p) < 0 {
+                    chain.pop();
+                }
+                chain.push(p);
+            }
+            hull.extend(chain);
+        }
+
+        // A site that occurs twice ties with itself everywhere, so its region is empty
+        self.sites.iter()
+            .map(|s| hull.contains(s) && self.sites.iter().filter(|o| *o == s).count() == 1)
+            .collect()
+    }
+
+    /// Count the points of a bounded Euclidean region row by row. The region is the intersection of
+    /// half-planes of points closer to its site than to each other site, so it's convex and the rows
+    /// that cross it are contiguous.
+    fn euclidean_area(&self, i: usize) -> usize {
+        let site = self.sites[i];
+        if self.sites.iter().filter(|s| **s == site).count() > 1 {
+            return 0;
+        }
+
+        let mut area = 0;
+        for (start, step) in [(site.1, 1), (site.1 - 1, -1)] {
+            let mut y = start;
+            while let Some((lo, hi)) = self.euclidean_row(i, y) {
+                area += (hi - lo + 1).max(0) as usize;
+                y += step;
+            }
+        }
+        area
+    }
+
+    /// Get the range of x on row y that is strictly closer to site i than to all other sites, or
+    /// None if the row doesn't cross the region at all, not even between grid points
+    fn euclidean_row(&self, i: usize, y: i64) -> Option<(i64, i64)> {
+        let s = self.sites[i];
+
+        // Every other site gives a bound a * x < c, kept as fractions (numerator, denominator)
+        let mut lower: Option<(i128, i128)> = None;
+        let mut upper: Option<(i128, i128)> = None;
+        let less = |a: (i128, i128), b: (i128, i128)| a.0 * b.1 < b.0 * a.1;
+
+        for (j, o) in self.sites.iter().enumerate() {
+            if j == i {
+                continue;
+            }
+            let a = 2 * (o.0 - s.0) as i128;
+            let c = (o.0 * o.0 + o.1 * o.1 - s.0 * s.0 - s.1 * s.1 - 2 * y * (o.1 - s.1)) as i128;
+            if a > 0 {
+                if upper.is_none_or(|u| less((c, a), u)) {
+                    upper = Some((c, a));
+                }
+            } else if a < 0 {
+                if lower.is_none_or(|l| less(l, (-c, -a))) {
+                    lower = Some((-c, -a));
+                }
+            } else if c <= 0 {
+                return None;
+            }
+        }
+
+        // A row that crosses a bounded region is bounded on both sides
+        let (lower, upper) = (lower.expect("unbounded region"), upper.expect("unbounded region"));
+        if !less(lower, upper) {
+            return None;
+        }
+
+        let lo = lower.0.div_euclid(lower.1) + 1;
+        let hi = (upper.0 - 1).div_euclid(upper.1);
+        Some((lo as i64, hi as i64))
+    }
+}
+
+fn bounds(points: &[Point]) -> (Point, Point) {
+    let xs = points.iter().map(|p| p.0);
+    let ys = points.iter().map(|p| p.1);
+    (
+        (xs.clone().min().unwrap(), ys.clone().min().unwrap()),
+        (xs.max().unwrap(), ys.max().unwrap()),
+    )
+}
+
+/// Pick a colour for a region, spreading the hues of consecutive regions with the golden ratio
+fn region_color(i: usize) -> [u8; 3] {
+    let hue = (i as f64 * 0.618_033_988_75).fract();
+    let channel = |offset: f64| (150.0 + 90.0 * (2.0 * PI * (hue - offset)).cos()) as u8;
+    [channel(0.0), channel(1.0 / 3.0), channel(2.0 / 3.0)]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_sites() -> Vec<Point> {
+        vec![(1, 1), (1, 6), (8, 3), (3, 4), (5, 5), (8, 9)]
+    }
+
+    /// Count region areas in a box that is much larger than the sites, as a reference
+    fn brute_force_areas(voronoi: &Voronoi) -> Vec<usize> {
+        let mut areas = vec![0; voronoi.sites.len()];
+        for y in -60..70 {
+            for x in -60..70 {
+                if let Some(i) = voronoi.nearest((x, y)) {
+                    areas[i] += 1;
+                }
+            }
+        }
+        areas
+    }
+
+    #[test]
+    fn test_render() {
+        let voronoi = Voronoi::new(get_sites(), Metric::Manhattan);
+        let expected = "\
+aaaaa.cccc
+aAaaa.cccc
+aaaddecccc
+aadddeccCc
+..dDdeeccc
+bb.deEeecc
+bBb.eeee..
+bbb.eeefff
+bbb.eeffff
+bbb.ffffFf
+";
+        assert_eq!(voronoi.render((0, 0), (9, 9)), expected);
+        assert!(voronoi.render_ppm((0, 0), (9, 9)).starts_with(b"P6\n10 10\n255\n"));
+    }
+
+    #[test]
+    fn test_regions() {
+        use self::Region::*;
+
+        let infinite = [Infinite, Infinite, Infinite];
+        let voronoi = Voronoi::new(get_sites(), Metric::Manhattan);
+        assert_eq!(voronoi.regions()[2..5], [Infinite, Finite(9), Finite(17)]);
+
+        // Sites on a line all have an unbounded region, except that the middle one is cut off
+        // between its neighbours under the Chebyshev metric
+        let line = vec![(0, 0), (5, 0), (10, 0)];
+        assert_eq!(Voronoi::new(line.clone(), Metric::Manhattan).regions(), infinite);
+        assert_eq!(Voronoi::new(line.clone(), Metric::Euclidean).regions(), infinite);
+        let regions = Voronoi::new(line, Metric::Chebyshev).regions();
+        assert_eq!(regions[0], Infinite);
+        assert!(matches!(regions[1], Finite(_)));
+
+        // Every finite region is counted exactly
+        for metric in [Metric::Manhattan, Metric::Chebyshev, Metric::Euclidean] {
+            let mut sites = get_sites();
+            sites.extend(vec![(4, 13), (-3, 6), (9, 6), (5, 5)]);
+            let voronoi = Voronoi::new(sites, metric);
+            let reference = brute_force_areas(&voronoi);
+            for (region, area) in voronoi.regions().into_iter().zip(reference) {
+                if let Finite(expected) = region {
+                    assert_eq!(area, expected, "{:?}", metric);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_total_distance_below() {
+        let voronoi = Voronoi::new(get_sites(), Metric::Manhattan);
+        assert_eq!(voronoi.total_distance_below(32.0), 16);
+
+        // With a large limit the region reaches far beyond the sites
+        for metric in [Metric::Manhattan, Metric::Chebyshev, Metric::Euclidean] {
+            let voronoi = Voronoi::new(get_sites(), metric);
+            let total = |p: Point| voronoi.sites.iter().map(|s| metric.distance(p, *s)).sum::<f64>();
+            let expected = (-60..70)
+                .flat_map(|y| (-60..70).map(move |x| (x, y)))
+                .filter(|p| total(*p) < 150.0)
+                .count();
+            assert_eq!(voronoi.total_distance_below(150.0), expected, "{:?}", metric);
+        }
+    }
+}